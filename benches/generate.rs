@@ -0,0 +1,64 @@
+mod legacy;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256StarStar;
+
+use string_studio::config::{CompositePattern, Config};
+use string_studio::generate::regex_gen::RegexGen;
+
+use self::legacy::TreeWalk;
+
+/// Strings generated per benchmark iteration.
+const BATCH: u64 = 1000;
+
+fn names_pattern() -> String {
+    let config = Config::default();
+    // Only whole parts are expanded, so the group wraps the fragment from
+    // parts of its own.
+    CompositePattern::from(&["@syllable@", "@syllable@", "(", "@syllable@", ")?"] as &[&str])
+        .assemble_pattern(&config.fragments)
+        .unwrap()
+}
+
+fn bench_randomize(c: &mut Criterion) {
+    let patterns = [
+        ("hex_id", String::from("[0-9a-f]{32}")),
+        ("names", names_pattern()),
+        ("unicode_words", String::from(r"\w{4,12}( \w{4,12}){0,3}")),
+        ("weighted", String::from("({=9}[bcdfg]|{=1}[aeiou]'{?0.2})+")),
+    ];
+    let mut group = c.benchmark_group("randomize");
+    group.throughput(Throughput::Elements(BATCH));
+    for (name, pattern) in &patterns {
+        let gen = RegexGen::new(pattern).unwrap();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let sample = gen.randomize(&mut rng).unwrap();
+        assert!(!sample.contains('@'), "{} generated `{}`", name, sample);
+        group.bench_function(BenchmarkId::new("program", name), |b| {
+            b.iter(|| {
+                for _ in 0..BATCH {
+                    criterion::black_box(gen.randomize(&mut rng).unwrap());
+                }
+            })
+        });
+        // The tree walk has no extensions, so patterns using them are only
+        // timed with the program.
+        if let Some(walk) = TreeWalk::new(pattern) {
+            // It's slow enough that fewer samples still give a steady time.
+            group.sample_size(10);
+            group.bench_function(BenchmarkId::new("tree_walk", name), |b| {
+                b.iter(|| {
+                    for _ in 0..BATCH {
+                        criterion::black_box(walk.randomize(&mut rng).unwrap());
+                    }
+                })
+            });
+            group.sample_size(100);
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_randomize);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! The tree walk strings were generated with before patterns were compiled
+//! into a program, kept only to time the program against.
+//!
+//! It clones each node of the parsed pattern as it visits it and rebuilds a
+//! class's characters on every visit, like the original did. That includes
+//! its class sampling, which only reaches the first byte of each range's
+//! UTF-8 encoding, so its strings aren't the ones the program generates.
+//! Extensions, back-references and length limits aren't supported.
+
+use rand::distributions::{Distribution, Uniform};
+use rand::prelude::*;
+use regex_syntax::hir::{self, Hir, HirKind};
+use regex_syntax::Parser;
+
+/// Most repetitions of `*`, `+` and `{n,}`.
+const MAX_REPEAT: u32 = 100;
+
+pub struct TreeWalk {
+    hir: Hir,
+}
+
+impl TreeWalk {
+    pub fn new(pattern: &str) -> Option<Self> {
+        Parser::new().parse(pattern).ok().map(|hir| Self { hir })
+    }
+
+    pub fn randomize(&self, rng: &mut impl Rng) -> Option<String> {
+        randomize_for(rng, self.hir.kind().clone())
+    }
+}
+
+fn repeat<R: Rng>(rng: &mut R, h: &Hir, min: u32, max: u32) -> Option<String> {
+    let n = Uniform::from(min..=max).sample(rng);
+    let parts: Option<Vec<String>> = (0..n).map(|_| randomize_for(rng, h.kind().clone())).collect();
+    parts.map(|p| p.join(""))
+}
+
+fn randomize_class<R: Rng>(rng: &mut R, cls: hir::ClassUnicode) -> Option<String> {
+    let mut chars: Vec<char> = Vec::new();
+    for r in cls.iter() {
+        let (s, e) = (r.start().to_string(), r.end().to_string());
+        for byte in s.as_bytes()[0]..=e.as_bytes()[0] {
+            if let Ok(s) = String::from_utf8(vec![byte]) {
+                chars.push(s.chars().next().unwrap());
+            }
+        }
+    }
+    chars.choose(rng).map(|c| c.to_string())
+}
+
+fn randomize_for<R: Rng>(rng: &mut R, kind: HirKind) -> Option<String> {
+    match kind {
+        HirKind::Empty | HirKind::Anchor(_) => Some(String::new()),
+        HirKind::WordBoundary(_) => Some(String::from(" ")),
+        HirKind::Literal(hir::Literal::Unicode(c)) => Some(c.to_string()),
+        HirKind::Class(hir::Class::Unicode(cls)) => randomize_class(rng, cls),
+        HirKind::Group(group) => randomize_for(rng, group.hir.kind().clone()),
+        HirKind::Concat(exprs) => {
+            let mut s = String::new();
+            for e in &exprs {
+                s += &randomize_for(rng, e.kind().clone())?;
+            }
+            Some(s)
+        }
+        HirKind::Alternation(mut exprs) => {
+            exprs.shuffle(rng);
+            randomize_for(rng, exprs[0].kind().clone())
+        }
+        HirKind::Repetition(rep) => {
+            let h = rep.hir.as_ref().clone();
+            match rep.kind {
+                hir::RepetitionKind::ZeroOrOne => repeat(rng, &h, 0, 1),
+                hir::RepetitionKind::ZeroOrMore => repeat(rng, &h, 0, MAX_REPEAT),
+                hir::RepetitionKind::OneOrMore => repeat(rng, &h, 1, MAX_REPEAT),
+                hir::RepetitionKind::Range(hir::RepetitionRange::Exactly(n)) => {
+                    repeat(rng, &h, n, n)
+                }
+                hir::RepetitionKind::Range(hir::RepetitionRange::AtLeast(n)) => {
+                    repeat(rng, &h, n, MAX_REPEAT.max(n))
+                }
+                hir::RepetitionKind::Range(hir::RepetitionRange::Bounded(min, max)) => {
+                    repeat(rng, &h, min, max)
+                }
+            }
+        }
+        _ => None,
+    }
+}
@@ -2,20 +2,30 @@ use clap::{App, Arg, SubCommand};
 use std::str::FromStr;
 
 use crate::config::*;
+use crate::error::Error;
 use crate::xform::Xform;
 
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(PartialEq)]
 pub enum AppAction {
     Root,
     Generate,
+    Enumerate,
+    Stats,
+    /// Train a Markov model on the word list at `words` and save it to
+    /// `model`.
+    Train {
+        words: PathBuf,
+        model: PathBuf,
+        order: usize,
+    },
     DumpConfig,
 }
 
 fn require_parsed_str<I: FromStr>(v: String, message: &str) -> Result<(), String> {
-    if let Ok(_) = v.parse::<I>() {
+    if v.parse::<I>().is_ok() {
         Ok(())
     } else {
         Err(String::from(message))
@@ -28,6 +38,29 @@ fn require_u32_str(v: String) -> Result<(), String> {
 fn require_u64_str(v: String) -> Result<(), String> {
     require_parsed_str::<u64>(v, "The value was not an integer or was out of range")
 }
+fn require_usize_str(v: String) -> Result<(), String> {
+    require_parsed_str::<usize>(v, "The value was not an integer or was out of range")
+}
+
+fn require_score_str(v: String) -> Result<(), String> {
+    match v.parse::<f64>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(()),
+        _ => Err(String::from("The value was not a number from 0 to 1")),
+    }
+}
+
+fn require_repeat_dist(v: String) -> Result<(), String> {
+    v.parse::<RepeatDist>().map(|_| ())
+}
+
+/// `path` joined to the current directory, so a relative path given on the
+/// command line isn't later taken as relative to the config directory.
+fn from_current_dir(path: &str) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => PathBuf::from(path),
+    }
+}
 
 fn require_existing_file(v: String) -> Result<(), String> {
     let p = Path::new(&v);
@@ -38,67 +71,336 @@ fn require_existing_file(v: String) -> Result<(), String> {
     }
 }
 
+fn number_arg<'a, 'b>(help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name("number")
+        .short("n")
+        .long("number")
+        .value_name("INTEGER")
+        .help(help)
+        .takes_value(true)
+        .validator(require_u32_str)
+        .default_value("15")
+}
+
+fn repeat_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("repeat")
+        .long("repeat")
+        .value_name("DISTRIBUTION")
+        .help("Sets how many extra times `*`, `+` and `{n,}` repeat: `uniform[:CAP]`, `geometric[:MEAN[:CAP]]`, `poisson[:MEAN[:CAP]]` or `normal[:MEAN[:STD_DEV[:CAP]]]`.")
+        .takes_value(true)
+        .validator(require_repeat_dist)
+}
+
+fn max_len_arg<'a, 'b>(help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name("max-len")
+        .long("max-len")
+        .value_name("INTEGER")
+        .help(help)
+        .takes_value(true)
+        .validator(require_usize_str)
+}
+
+fn min_len_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("min-len")
+        .long("min-len")
+        .value_name("INTEGER")
+        .help("Sets the length of the shortest string to output, after transforms")
+        .takes_value(true)
+        .validator(require_usize_str)
+}
+
+fn length_unit_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("length-unit")
+        .long("length-unit")
+        .value_name("UNIT")
+        .help("Sets what `--min-len` and `--max-len` count")
+        .takes_value(true)
+        .possible_values(&["chars", "graphemes"])
+}
+
+fn frequencies_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("frequencies")
+        .long("frequencies")
+        .value_name("TABLE")
+        .help("Weights the characters of classes and single-character alternations by a frequency table: `english`, `french`, `german`, `spanish`, or one from the config file")
+        .takes_value(true)
+}
+
+fn charset_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("charset")
+        .long("charset")
+        .value_name("CHARSET")
+        .help("Limits character classes like `.`, `\\w` and `\\d` to a charset: `ascii-printable`, `latin1`, `no-ambiguous`, or one from the config file")
+        .takes_value(true)
+}
+
+fn backend_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("backend")
+        .long("backend")
+        .value_name("BACKEND")
+        .help("Sets the backend to generate strings with. Defaults to `regex`, which generates strings matching the pattern.")
+        .takes_value(true)
+}
+
+fn model_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("model")
+        .long("model")
+        .value_name("FILE")
+        .help("Generates words from a Markov model made by `train` instead of a pattern. Relative paths are in the config directory.")
+        .takes_value(true)
+}
+
+fn novel_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("novel")
+        .long("novel")
+        .help("Leaves out the words a `--model` was trained on")
+}
+
+fn grammar_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("grammar")
+        .long("grammar")
+        .value_name("GRAMMAR")
+        .help("Generates strings from a grammar instead of a pattern: `place`, or one from the config file")
+        .takes_value(true)
+}
+
+fn phonology_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("phonology")
+        .long("phonology")
+        .value_name("PHONOLOGY")
+        .help("Builds words syllable by syllable from a phonology instead of a pattern: `basic`, or one from the config file")
+        .takes_value(true)
+}
+
+fn sampling_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("sampling")
+        .long("sampling")
+        .value_name("MODE")
+        .help("Sets how strings are drawn: `per_node` picks each branch and repetition count in turn, `uniform` makes every matching string equally likely.")
+        .takes_value(true)
+        .possible_values(&["per_node", "uniform"])
+}
+
+fn xform_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("xform")
+        .long("xform")
+        .short("x")
+        .value_name("XFORM_NAME")
+        .help("Adds a transformation to the generated strings.")
+        .takes_value(true)
+        .possible_values(&["u_after_q", "lower_case", "upper_case", "title_case"])
+        .multiple(true)
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Sets the output format")
+        .takes_value(true)
+        .possible_values(if cfg!(feature = "table_format") {
+            &["simple", "table", "json", "csv"]
+        } else {
+            &["simple", "json", "csv"]
+        })
+        .default_value("simple")
+}
+
+fn pattern_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("pattern")
+        .value_name("PATTERN")
+        .help("Sets the pattern to generate strings from. If multiple values are supplied, they will be concatenated. Pattern fragments must be separate values (one argument for each fragment).")
+        .required(true)
+        .multiple(true)
+        .index(1)
+}
+
+fn pretty_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("pretty")
+        .long("pretty")
+        .help("Use nice formatting when `--format` is `json`")
+}
+
 fn make_gen_subcommand<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("gen")
-            .version("0.1.0")
-            .author("Zachary Frost")
-            .about("Generate randomized strings from regular expression patterns")
-            .arg(
-                Arg::with_name("number")
-                    .short("n")
-                    .long("number")
-                    .value_name("INTEGER")
-                    .help("Sets the number of strings to generate")
-                    .takes_value(true)
-                    .validator(require_u32_str)
-                    .default_value("15"),
-            )
-            .arg(
-                Arg::with_name("seed")
-                    .long("seed")
-                    .value_name("INTEGER")
-                    .help("Sets the random seed to use when generating strings. Set to 0 to auto pick seed.")
-                    .takes_value(true)
-                    .validator(require_u64_str)
-                    .default_value("0"),
-            )
-            .arg(
-                Arg::with_name("xform")
-                    .long("xform")
-                    .short("x")
-                    .value_name("XFORM_NAME")
-                    .help("Adds a transformation to the generated strings.")
-                    .takes_value(true)
-                    .possible_values(&["u_after_q", "lower_case", "upper_case", "title_case"])
-                    .multiple(true)
-            )
-            .arg(
-                Arg::with_name("format")
-                    .short("f")
-                    .long("format")
-                    .value_name("FORMAT")
-                    .help("Sets the output format")
-                    .takes_value(true)
-                    .possible_values(if cfg!(feature = "table_format") {
-                        &["simple", "table", "json", "csv"]
-                    } else {
-                        &["simple", "json", "csv"]
-                    })
-                    .default_value("simple"),
-            )
-            .arg(
-                Arg::with_name("pattern")
-                    .value_name("PATTERN")
-                    .help("Sets the pattern to generate strings from. If multiple values are supplied, they will be concatenated. Pattern fragments must be separate values (one argument for each fragment).")
-                    .required(true)
-                    .multiple(true)
-                    .index(1)
-            )
-            .arg(
-                Arg::with_name("pretty")
-                    .long("pretty")
-                    .help("Use nice formatting when `--format` is `json`"),
-            )
+        .version("0.1.0")
+        .author("Zachary Frost")
+        .about("Generate randomized strings from regular expression patterns")
+        .arg(number_arg("Sets the number of strings to generate. Set to 0 to keep generating until the output is closed."))
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("INTEGER")
+                .help("Sets the random seed to use when generating strings. Set to 0 to auto pick seed.")
+                .takes_value(true)
+                .validator(require_u64_str)
+                .default_value("0"),
+        )
+        .arg(repeat_arg())
+        .arg(sampling_arg())
+        .arg(
+            Arg::with_name("unique")
+                .long("unique")
+                .short("u")
+                .help("Don't output any string more than once"),
+        )
+        .arg(
+            Arg::with_name("unique-retries")
+                .long("unique-retries")
+                .value_name("INTEGER")
+                .help("Sets how many duplicates in a row `--unique` tolerates before giving up")
+                .takes_value(true)
+                .validator(require_u32_str),
+        )
+        .arg(
+            Arg::with_name("min-distance")
+                .long("min-distance")
+                .value_name("INTEGER")
+                .help("Leaves out strings fewer than this many edits from one already output or in `--existing`, ignoring case")
+                .takes_value(true)
+                .validator(require_usize_str),
+        )
+        .arg(
+            Arg::with_name("phonetic")
+                .long("phonetic")
+                .help("Leaves out strings that sound like one already output or in `--existing`, like `Calor` after `Kalor`, and exact repeats unless `--min-distance` is given"),
+        )
+        .arg(
+            Arg::with_name("existing")
+                .long("existing")
+                .value_name("FILE")
+                .help("Leaves out strings matching a name in this file, with one per line, or as close to one as `--min-distance` and `--phonetic` forbid")
+                .takes_value(true)
+                .validator(require_existing_file),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .value_name("INTEGER")
+                .help("Sets the number of threads to generate strings on. Set to 0 to use one per CPU. The output is the same whatever the number.")
+                .takes_value(true)
+                .validator(require_usize_str),
+        )
+        .arg(frequencies_arg())
+        .arg(charset_arg())
+        .arg(backend_arg())
+        .arg(model_arg())
+        .arg(novel_arg())
+        .arg(grammar_arg())
+        .arg(phonology_arg())
+        .arg(
+            Arg::with_name("score")
+                .long("score")
+                .help("Rates how pronounceable each string is, from 0 to 1, and adds the score to `json`, `csv` and `table` output"),
+        )
+        .arg(
+            Arg::with_name("min-score")
+                .long("min-score")
+                .value_name("SCORE")
+                .help("Only outputs strings with a pronounceability score of at least this, from 0 to 1")
+                .takes_value(true)
+                .validator(require_score_str),
+        )
+        .arg(
+            Arg::with_name("rank")
+                .long("rank")
+                .value_name("INTEGER")
+                .help("Keeps only this many of the `--number` strings generated, the most pronounceable first")
+                .takes_value(true)
+                .validator(require_u32_str),
+        )
+        .arg(
+            Arg::with_name("score-model")
+                .long("score-model")
+                .value_name("FILE")
+                .help("Learns pronounceability scores from the words a Markov model made by `train` was trained on, instead of common English words. Relative paths are in the config directory.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("blocklist")
+                .long("blocklist")
+                .help("Leaves out strings containing profanity, slurs and other words from the built-in blocklist, even in leetspeak or with diacritics"),
+        )
+        .arg(
+            Arg::with_name("blocklist-file")
+                .long("blocklist-file")
+                .value_name("FILE")
+                .help("Also leaves out strings containing a word from this file, with one word per line. Relative paths are in the config directory. Implies `--blocklist`.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(min_len_arg())
+        .arg(max_len_arg("Sets the length of the longest string to generate, after transforms. Required by `--sampling uniform` for patterns that can generate strings of any length."))
+        .arg(length_unit_arg())
+        .arg(xform_arg())
+        .arg(format_arg())
+        .arg(pattern_arg().required_unless_one(&["model", "grammar", "phonology"]))
+        .arg(pretty_arg())
+}
+
+fn make_enumerate_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("enumerate")
+        .version("0.1.0")
+        .author("Zachary Frost")
+        .about("List every string a pattern can generate, shortest first")
+        .arg(charset_arg())
+        .arg(min_len_arg())
+        .arg(max_len_arg("Sets the length of the longest strings to list, after transforms. Required for patterns that can generate strings of any length."))
+        .arg(length_unit_arg())
+        .arg(xform_arg())
+        .arg(format_arg())
+        .arg(pattern_arg())
+        .arg(pretty_arg())
+}
+
+fn make_stats_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("stats")
+        .version("0.1.0")
+        .author("Zachary Frost")
+        .about("Report how many strings a pattern can generate and how likely duplicates are")
+        .arg(number_arg("Sets the number of strings to report the chance of a duplicate for"))
+        .arg(repeat_arg())
+        .arg(sampling_arg())
+        .arg(frequencies_arg())
+        .arg(charset_arg())
+        .arg(max_len_arg("Only count strings up to this length, in characters"))
+        .arg(format_arg())
+        .arg(pattern_arg())
+        .arg(pretty_arg())
+}
+
+fn make_train_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("train")
+        .version("0.1.0")
+        .author("Zachary Frost")
+        .about("Train a Markov model on a list of words, for `gen --model`")
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("INTEGER")
+                .help("Sets how many characters before each one the model looks at. Higher orders stay closer to the training words.")
+                .takes_value(true)
+                .validator(require_usize_str)
+                .default_value("3"),
+        )
+        .arg(
+            Arg::with_name("words")
+                .value_name("WORDS")
+                .help("Sets the file to train on, with one word per line. Relative paths are from the current directory.")
+                .required(true)
+                .validator(require_existing_file)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("model")
+                .value_name("MODEL")
+                .help("Sets the file to save the model to. Relative paths are in the config directory.")
+                .required(true)
+                .index(2),
+        )
 }
 
 fn make_dumpcfg_subcommand<'a, 'b>() -> App<'a, 'b> {
@@ -108,7 +410,7 @@ fn make_dumpcfg_subcommand<'a, 'b>() -> App<'a, 'b> {
         .about("Overwrite the user config file with the default configuration.")
 }
 
-pub fn process_args() -> Result<(AppAction, Config), String> {
+pub fn process_args() -> Result<(AppAction, Config), Error> {
     let cfg_path = get_cfg_file_path();
 
     let app = App::new("String Studio")
@@ -139,30 +441,57 @@ pub fn process_args() -> Result<(AppAction, Config), String> {
                 .help("Sets the level of verbosity. Repeat to increase level (capped at 2)."),
         )
         .subcommand(make_gen_subcommand())
+        .subcommand(make_enumerate_subcommand())
+        .subcommand(make_stats_subcommand())
+        .subcommand(make_train_subcommand())
         .subcommand(make_dumpcfg_subcommand())
     ;
     let matches = app.get_matches();
 
     let verbosity = matches.occurrences_of("verbosity") as u8;
+    // Relative data paths are resolved against the directory of the config
+    // file given, or the default one.
+    let config_dir = matches
+        .value_of("config")
+        .and_then(|path| Path::new(path).parent())
+        .map(Path::to_path_buf);
 
     let action: AppAction = match matches.subcommand_name() {
         Some("gen") => AppAction::Generate,
+        Some("enumerate") => AppAction::Enumerate,
+        Some("stats") => AppAction::Stats,
+        Some("train") => {
+            let train_matches = matches.subcommand_matches("train").unwrap();
+            let dir_config = Config {
+                config_dir: config_dir.clone(),
+                ..Default::default()
+            };
+            // The words are read where the user is, and the model saved where
+            // `gen --model` looks for it.
+            AppAction::Train {
+                words: PathBuf::from(train_matches.value_of("words").unwrap()),
+                model: dir_config.resolve_path(Path::new(train_matches.value_of("model").unwrap())),
+                order: train_matches.value_of("order").unwrap().parse().unwrap(),
+            }
+        }
         Some("dump_cfg") => AppAction::DumpConfig,
         _ => AppAction::Root,
     };
-    if let Some(sub_matches) = matches.subcommand_matches("gen") {
-        let num = sub_matches.value_of("number");
-        if let None = num {
-            return Err(String::from(
-                "No value found for `number`! This should not happen.",
-            ));
-        }
-        let num = num.unwrap().parse::<u32>().unwrap_or(1);
+    let sub_matches = match action {
+        AppAction::Generate => matches.subcommand_matches("gen"),
+        AppAction::Enumerate => matches.subcommand_matches("enumerate"),
+        AppAction::Stats => matches.subcommand_matches("stats"),
+        _ => None,
+    };
+    if let Some(sub_matches) = sub_matches {
+        let num = sub_matches
+            .value_of("number")
+            .map_or(15, |n| n.parse::<u32>().unwrap_or(1));
         let format = sub_matches.value_of("format");
-        if let None = format {
-            return Err(String::from(
+        if format.is_none() {
+            return Err(Error::Args(String::from(
                 "No value found for `format`! This should not happen.",
-            ));
+            )));
         }
 
         let pretty = sub_matches.is_present("pretty");
@@ -175,7 +504,7 @@ pub fn process_args() -> Result<(AppAction, Config), String> {
                 let s = x.as_str();
                 match Xform::try_from(s) {
                     Ok(x) => x,
-                    Err(s) => panic!(s),
+                    Err(s) => panic!("{}", s),
                 }
             })
             .collect();
@@ -186,49 +515,222 @@ pub fn process_args() -> Result<(AppAction, Config), String> {
             .parse::<u64>()
             .unwrap_or(0);
     
+        let repeat = sub_matches
+            .value_of("repeat")
+            .map(|r| r.parse::<RepeatDist>().unwrap());
+        let sampling = sub_matches
+            .value_of("sampling")
+            .map(|s| s.parse::<Sampling>().unwrap());
+        let unique = sub_matches.is_present("unique");
+        let unique_retries = sub_matches
+            .value_of("unique-retries")
+            .map(|n| n.parse::<u32>().unwrap());
+        let min_distance = sub_matches
+            .value_of("min-distance")
+            .map(|n| n.parse::<usize>().unwrap());
+        let phonetic = sub_matches.is_present("phonetic");
+        let existing_names = sub_matches.value_of("existing").map(from_current_dir);
+        let jobs = sub_matches
+            .value_of("jobs")
+            .map(|n| n.parse::<usize>().unwrap());
+        let frequencies = sub_matches.value_of("frequencies").map(String::from);
+        let charset = sub_matches.value_of("charset").map(String::from);
+        let backend = sub_matches.value_of("backend").map(String::from);
+        let model = sub_matches.value_of("model").map(PathBuf::from);
+        let novel = sub_matches.is_present("novel");
+        let grammar = sub_matches.value_of("grammar").map(String::from);
+        let phonology = sub_matches.value_of("phonology").map(String::from);
+        let score = sub_matches.is_present("score");
+        let min_score = sub_matches
+            .value_of("min-score")
+            .map(|n| n.parse::<f64>().unwrap());
+        let rank = sub_matches
+            .value_of("rank")
+            .map(|n| n.parse::<u32>().unwrap());
+        let score_model = sub_matches.value_of("score-model").map(PathBuf::from);
+        let blocklists: Vec<PathBuf> = sub_matches
+            .values_of("blocklist-file")
+            .map_or(Vec::new(), |files| files.map(PathBuf::from).collect());
+        let blocklist = sub_matches.is_present("blocklist") || !blocklists.is_empty();
+        let min_len = sub_matches
+            .value_of("min-len")
+            .map(|n| n.parse::<usize>().unwrap());
+        let max_len = sub_matches
+            .value_of("max-len")
+            .map(|n| n.parse::<usize>().unwrap());
+        let length_unit = sub_matches
+            .value_of("length-unit")
+            .map(|u| u.parse::<LengthUnit>().unwrap());
+
         let pattern = CompositePattern::from(pattern.as_slice());
         let cmd_config = Config {
             format: OutputFormat::from(format.unwrap()),
             number: num,
             verbosity: Verbosity::from(verbosity),
             pattern: pattern.clone(),
-            pretty: pretty,
+            pretty,
             fragments: Default::default(),
+            frequency_tables: Default::default(),
+            frequencies: frequencies.clone(),
+            charsets: Default::default(),
+            charset: charset.clone(),
+            backend: backend.clone(),
+            model: model.clone(),
+            novel,
+            grammars: Default::default(),
+            grammar: grammar.clone(),
+            phonologies: Default::default(),
+            phonology: phonology.clone(),
+            backends: Default::default(),
             seed,
             xforms: Default::default(),
+            repeat: repeat.clone().unwrap_or_default(),
+            sampling: sampling.unwrap_or_default(),
+            unique,
+            unique_retries: unique_retries.unwrap_or(DEFAULT_UNIQUE_RETRIES),
+            min_distance,
+            phonetic,
+            existing_names: existing_names.clone(),
+            min_len,
+            max_len,
+            length_unit: length_unit.unwrap_or_default(),
+            score,
+            min_score,
+            rank,
+            score_model: score_model.clone(),
+            blocklist,
+            blocklists: blocklists.clone(),
+            jobs: jobs.unwrap_or(1),
+            config_dir: None,
         };
-        let cfg = if let Some(cfg_path) = matches.value_of("config") {
-            if let Ok(s) = fs::read_to_string(cfg_path) {
-                if let Ok(mut c) = serde_json::from_str::<Config>(s.as_str()) {
-                    c.verbosity = Verbosity::from(verbosity); // Ignore verbosity in config file
-                    c.pattern = pattern.clone(); // Ignore pattern in config file
-                    c.seed = seed; // Ignore seed in config file
-                    c.xforms = xforms;
-                    c.number = num;
-
-                    if sub_matches.occurrences_of("format") > 0 {
-                        c.format = OutputFormat::from(format.unwrap());
-                    }
-                    if sub_matches.is_present("pretty") {
-                        c.pretty = true;
-                    }
-
-                    c
-                } else {
-                    return Err(String::from("Failed to parse config file!"));
+        // A config file that can't be read is skipped, as if none was given.
+        let cfg_file = matches.value_of("config").and_then(|cfg_path| {
+            let cfg_path = PathBuf::from(cfg_path);
+            match fs::read_to_string(&cfg_path) {
+                Ok(s) => Some((cfg_path, s)),
+                Err(source) => {
+                    let error = Error::ConfigIo {
+                        path: cfg_path,
+                        source,
+                    };
+                    eprintln_v1(
+                        &cmd_config,
+                        format!("Warning: {}; using the command line options alone", error).as_str(),
+                    );
+                    None
                 }
-            } else {
-                cmd_config
             }
+        });
+        let cfg = if let Some((cfg_path, s)) = cfg_file {
+            let mut c = match serde_json::from_str::<Config>(s.as_str()) {
+                Ok(c) => c,
+                Err(source) => {
+                    return Err(Error::ConfigJson {
+                        path: cfg_path,
+                        source,
+                    })
+                }
+            };
+            c.config_dir = config_dir;
+            c.verbosity = Verbosity::from(verbosity); // Ignore verbosity in config file
+            c.pattern = pattern.clone(); // Ignore pattern in config file
+            c.seed = seed; // Ignore seed in config file
+            c.xforms = xforms;
+            c.number = num;
+
+            if sub_matches.occurrences_of("format") > 0 {
+                c.format = OutputFormat::from(format.unwrap());
+            }
+            if sub_matches.is_present("pretty") {
+                c.pretty = true;
+            }
+            if let Some(repeat) = repeat {
+                c.repeat = repeat;
+            }
+            if let Some(sampling) = sampling {
+                c.sampling = sampling;
+            }
+            if unique {
+                c.unique = true;
+            }
+            if min_len.is_some() {
+                c.min_len = min_len;
+            }
+            if max_len.is_some() {
+                c.max_len = max_len;
+            }
+            if let Some(length_unit) = length_unit {
+                c.length_unit = length_unit;
+            }
+            if let Some(unique_retries) = unique_retries {
+                c.unique_retries = unique_retries;
+            }
+            if let Some(jobs) = jobs {
+                c.jobs = jobs;
+            }
+            if min_distance.is_some() {
+                c.min_distance = min_distance;
+            }
+            if phonetic {
+                c.phonetic = true;
+            }
+            if existing_names.is_some() {
+                c.existing_names = existing_names;
+            }
+            if frequencies.is_some() {
+                c.frequencies = frequencies;
+            }
+            if charset.is_some() {
+                c.charset = charset;
+            }
+            if backend.is_some() {
+                c.backend = backend;
+            }
+            if model.is_some() {
+                c.model = model;
+            }
+            if novel {
+                c.novel = true;
+            }
+            if grammar.is_some() {
+                c.grammar = grammar;
+            }
+            if phonology.is_some() {
+                c.phonology = phonology;
+            }
+            if score {
+                c.score = true;
+            }
+            if min_score.is_some() {
+                c.min_score = min_score;
+            }
+            if rank.is_some() {
+                c.rank = rank;
+            }
+            if score_model.is_some() {
+                c.score_model = score_model;
+            }
+            if blocklist {
+                c.blocklist = true;
+            }
+            c.blocklists.extend(blocklists);
+
+            c
         } else {
             cmd_config
         };
 
+        Ok((action, cfg))
+    } else if let AppAction::Train { .. } = action {
+        let cfg = Config {
+            verbosity: Verbosity::from(verbosity),
+            ..Default::default()
+        };
         Ok((action, cfg))
     } else if action == AppAction::DumpConfig {
         let cfg = Config::default();
         Ok((AppAction::DumpConfig, cfg))
     } else {
-        Err(String::from("Failed to process cmd args!"))
+        Err(Error::Args(String::from("Failed to process cmd args!")))
     }
 }
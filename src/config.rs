@@ -1,18 +1,38 @@
+mod charset;
 mod fragment;
+mod frequency;
+mod grammar;
+mod length_unit;
 mod output_format;
+mod phonology;
+mod repeat_dist;
+mod sampling;
 mod verbose;
+mod word_list;
 
+pub use self::charset::*;
 pub use self::fragment::*;
+pub use self::frequency::*;
+pub use self::grammar::*;
+pub use self::length_unit::*;
 pub use self::output_format::*;
+pub use self::phonology::*;
+pub use self::repeat_dist::*;
+pub use self::sampling::*;
 pub use self::verbose::*;
+pub use self::word_list::*;
 use std::fmt::{self, Display};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub use crate::pattern::*;
 pub use crate::xform::*;
 
+use regex_syntax::hir::ClassUnicode;
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+use crate::generate::backend::Backends;
+
 fn default_format() -> OutputFormat {
     OutputFormat::Simple
 }
@@ -22,6 +42,15 @@ fn default_verbosity() -> Verbosity {
 fn default_number() -> u32 {
     15
 }
+/// How many duplicates in a row `--unique` tolerates by default.
+pub const DEFAULT_UNIQUE_RETRIES: u32 = 1000;
+
+fn default_unique_retries() -> u32 {
+    DEFAULT_UNIQUE_RETRIES
+}
+fn default_jobs() -> usize {
+    1
+}
 fn default_pretty() -> bool {
     false
 }
@@ -96,7 +125,7 @@ fn default_fragments() -> Vec<Fragment> {
     ]
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_format")]
     pub format: OutputFormat,
@@ -117,8 +146,197 @@ pub struct Config {
     #[serde(default)]
     pub fragments: Vec<Fragment>,
 
+    /// Character frequency tables, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frequency_tables: Vec<FrequencyTable>,
+    /// Name of the frequency table to weight characters by, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequencies: Option<String>,
+
+    /// Charsets, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub charsets: Vec<Charset>,
+    /// Name of the charset to limit classes in patterns to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+
+    /// Name of the backend to generate strings with, if not the regex one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// Markov model to generate words from, relative to the config
+    /// directory. Picks the `markov` backend unless another is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<PathBuf>,
+    /// Whether to leave out the words a model was trained on.
+    #[serde(default)]
+    pub novel: bool,
+    /// Grammars, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grammars: Vec<Grammar>,
+    /// Name of the grammar to generate strings from. Picks the `grammar`
+    /// backend unless another is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
+    /// Phonologies, on top of the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phonologies: Vec<Phonology>,
+    /// Name of the phonology to build words from. Picks the `phonology`
+    /// backend unless another is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phonology: Option<String>,
+    /// Backends registered by library users.
+    #[serde(default, skip)]
+    pub backends: Backends,
+
     #[serde(default, skip)]
     pub xforms: Vec<Xform>,
+
+    #[serde(default)]
+    pub repeat: RepeatDist,
+
+    #[serde(default)]
+    pub sampling: Sampling,
+
+    /// Whether to leave out strings that were already generated.
+    #[serde(default)]
+    pub unique: bool,
+    /// How many duplicates in a row `unique` tolerates before giving up, or
+    /// near-duplicates in a row for the diversity options.
+    #[serde(default = "default_unique_retries")]
+    pub unique_retries: u32,
+    /// Fewest edits a string may be from each one already generated or in
+    /// `existing_names`, ignoring case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_distance: Option<usize>,
+    /// Whether to leave out strings that sound like one already generated
+    /// or in `existing_names`. Without `min_distance`, exact repeats are
+    /// left out too, as if it were 1.
+    #[serde(default)]
+    pub phonetic: bool,
+    /// File of names, one per line, that strings have to be unlike too. A
+    /// relative path in the config file is relative to its directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub existing_names: Option<PathBuf>,
+
+    /// Shortest string to output, measured in `length_unit` after any
+    /// transforms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_len: Option<usize>,
+    /// Longest string to output, measured in `length_unit` after any
+    /// transforms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    #[serde(default)]
+    pub length_unit: LengthUnit,
+
+    /// Whether to rate how pronounceable each string is and output the
+    /// score with it.
+    #[serde(default)]
+    pub score: bool,
+    /// Lowest pronounceability score, from 0 to 1, of a string to output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f64>,
+    /// How many of the `number` strings generated to keep, the most
+    /// pronounceable first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u32>,
+    /// Markov model whose training words scores are learned from, relative
+    /// to the config directory, instead of the built-in English words.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_model: Option<PathBuf>,
+
+    /// Whether to leave out strings containing a word from the built-in
+    /// blocklist or `blocklists`.
+    #[serde(default)]
+    pub blocklist: bool,
+    /// Files of more words to block when `blocklist` is on, one per line,
+    /// relative to the config directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocklists: Vec<PathBuf>,
+
+    /// Threads to generate strings on, or 0 for one per CPU. The output is
+    /// the same whatever the number.
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+
+    /// Directory of the loaded config file, which relative data paths are
+    /// resolved against.
+    #[serde(default, skip)]
+    pub config_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// `path`, resolved against the directory of the loaded config file if
+    /// it's relative.
+    pub fn resolve_path(&self, path: &Path) -> PathBuf {
+        match &self.config_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The character weights of the configured frequency table, if any.
+    pub fn frequencies(&self) -> Result<Option<Frequencies>, Error> {
+        let name = match &self.frequencies {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let builtins = FrequencyTable::builtins();
+        match self.frequency_tables.iter().chain(&builtins).find(|t| t.name == *name) {
+            Some(table) if table.corpus.is_some() => {
+                let table = FrequencyTable {
+                    corpus: table.corpus.as_deref().map(|c| self.resolve_path(c)),
+                    ..table.clone()
+                };
+                table.resolve().map(Some)
+            }
+            Some(table) => table.resolve().map(Some),
+            None => Err(Error::Config(format!("No frequency table named `{}`", name))),
+        }
+    }
+
+    /// The characters of the configured charset, if any.
+    pub fn charset(&self) -> Result<Option<ClassUnicode>, Error> {
+        let name = match &self.charset {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let builtins = Charset::builtins();
+        match self.charsets.iter().chain(&builtins).find(|c| c.name == *name) {
+            Some(charset) => charset.resolve().map(Some),
+            None => Err(Error::Config(format!("No charset named `{}`", name))),
+        }
+    }
+
+    /// The configured phonology, if any.
+    pub fn phonology(&self) -> Result<Option<Phonology>, Error> {
+        let name = match &self.phonology {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        match self.phonologies.iter().find(|p| p.name == *name) {
+            Some(phonology) => Ok(Some(phonology.clone())),
+            None => match Phonology::builtins().into_iter().find(|p| p.name == *name) {
+                Some(phonology) => Ok(Some(phonology)),
+                None => Err(Error::Config(format!("No phonology named `{}`", name))),
+            },
+        }
+    }
+
+    /// The configured grammar, if any.
+    pub fn grammar(&self) -> Result<Option<Grammar>, Error> {
+        let name = match &self.grammar {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        match self.grammars.iter().find(|g| g.name == *name) {
+            Some(grammar) => Ok(Some(grammar.clone())),
+            None => match Grammar::builtins().into_iter().find(|g| g.name == *name) {
+                Some(grammar) => Ok(Some(grammar)),
+                None => Err(Error::Config(format!("No grammar named `{}`", name))),
+            },
+        }
+    }
 }
 
 impl Display for Config {
@@ -140,7 +358,37 @@ impl Default for Config {
             pretty: default_pretty(),
             seed: 0,
             fragments: default_fragments(),
+            frequency_tables: Vec::new(),
+            frequencies: None,
+            charsets: Vec::new(),
+            charset: None,
+            backend: None,
+            model: None,
+            novel: false,
+            grammars: Vec::new(),
+            grammar: None,
+            phonologies: Vec::new(),
+            phonology: None,
+            backends: Default::default(),
             xforms: default_xforms(),
+            repeat: Default::default(),
+            sampling: Default::default(),
+            unique: false,
+            unique_retries: default_unique_retries(),
+            min_distance: None,
+            phonetic: false,
+            existing_names: None,
+            min_len: None,
+            max_len: None,
+            length_unit: Default::default(),
+            score: false,
+            min_score: None,
+            rank: None,
+            score_model: None,
+            blocklist: false,
+            blocklists: Vec::new(),
+            jobs: default_jobs(),
+            config_dir: None,
         }
     }
 }
@@ -154,11 +402,11 @@ pub fn println_verbosity(verbosity: Verbosity, config: &Config, message: &str) {
 #[cfg(feature = "color_messages")]
 pub fn println_err(message: &str) {
     use termion::color::{Fg, Red, Reset as ResetColor};
-    println!("{}{}{}", Fg(Red), message, Fg(ResetColor));
+    eprintln!("{}{}{}", Fg(Red), message, Fg(ResetColor));
 }
 #[cfg(not(feature = "color_messages"))]
 pub fn println_err(message: &str) {
-    println!("{}", message);
+    eprintln!("{}", message);
 }
 pub fn println_v0(config: &Config, message: &str) {
     println_verbosity(Verbosity::NotVerbose, config, message);
@@ -170,6 +418,17 @@ pub fn println_v2(config: &Config, message: &str) {
     println_verbosity(Verbosity::VeryVerbose, config, message);
 }
 
+/// Print `message` to stderr at `verbosity` or above, for messages that can
+/// come while strings are being written to stdout.
+pub fn eprintln_verbosity(verbosity: Verbosity, config: &Config, message: &str) {
+    if config.verbosity >= verbosity {
+        eprintln!("{}", message);
+    }
+}
+pub fn eprintln_v1(config: &Config, message: &str) {
+    eprintln_verbosity(Verbosity::Verbose, config, message);
+}
+
 pub fn get_cfg_file_path() -> Option<PathBuf> {
     use directories::ProjectDirs;
 
@@ -0,0 +1,64 @@
+use regex_syntax::hir::{self, HirKind};
+use regex_syntax::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// A named set of characters that the classes in patterns get limited to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Charset {
+    pub name: String,
+    /// The characters, as a character class like `[a-z0-9]`.
+    pub class: String,
+}
+
+impl Charset {
+    pub fn new(name: &str, class: &str) -> Self {
+        Self {
+            name: String::from(name),
+            class: String::from(class),
+        }
+    }
+
+    /// The charsets that are always available.
+    pub fn builtins() -> Vec<Self> {
+        vec![
+            Self::new("ascii-printable", "[ -~]"),
+            Self::new("latin1", r"[ -~\xA0-\xFF]"),
+            Self::new("no-ambiguous", "[ -~&&[^0Ol1]]"),
+        ]
+    }
+
+    /// Parse the class.
+    pub fn resolve(&self) -> Result<hir::ClassUnicode, Error> {
+        let invalid = || {
+            Error::Config(format!(
+                "Charset `{}` needs a character class like `[a-z0-9]`, not `{}`",
+                self.name, self.class
+            ))
+        };
+        let hir = Parser::new().parse(&self.class).map_err(|_| invalid())?;
+        match hir.into_kind() {
+            HirKind::Class(hir::Class::Unicode(cls)) if !cls.ranges().is_empty() => Ok(cls),
+            HirKind::Literal(hir::Literal::Unicode(c)) => {
+                Ok(hir::ClassUnicode::new(vec![hir::ClassUnicodeRange::new(c, c)]))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charset_test() {
+        let no_ambiguous = Charset::builtins().remove(2).resolve().unwrap();
+        let has = |c: char| no_ambiguous.iter().any(|r| r.start() <= c && c <= r.end());
+        assert!(has('a') && has('~') && has('I'));
+        assert!(!has('0') && !has('O') && !has('l') && !has('1') && !has('\n'));
+        assert!(Charset::new("bad", "abc").resolve().is_err());
+        assert!(Charset::new("empty", "[a&&b]").resolve().is_err());
+    }
+}
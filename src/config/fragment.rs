@@ -1,11 +1,48 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 use crate::pattern::CompositePattern;
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// One weighted choice of a fragment made of alternatives.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alternative {
+    pub pattern: CompositePattern,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+impl Alternative {
+    pub fn new(pattern: CompositePattern, weight: f64) -> Self {
+        Self { pattern, weight }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Fragment {
     pub name: String,
+    #[serde(default, skip_serializing_if = "CompositePattern::is_empty")]
     pub pattern: CompositePattern,
+    /// Weighted choices used instead of `pattern` when not empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
+    /// A file with one entry per line, relative to the config directory, to
+    /// pick one of instead of `pattern`. A tab and a number after an entry
+    /// gives it a weight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wordlist: Option<PathBuf>,
+    /// A backend to generate the fragment's strings with, like `markov`,
+    /// instead of the one generating the pattern it's in. The backend reads
+    /// its settings, like `model`, from the rest of the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// Makes the fragment optional, included with this probability.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
     #[serde(default)]
     pub description: String,
 }
@@ -16,16 +53,7 @@ impl Fragment {
             name: String::from(name),
             pattern,
             description: String::from(description),
-        }
-    }
-}
-
-impl Default for Fragment {
-    fn default() -> Self {
-        Self {
-            name: String::new(),
-            pattern: Default::default(),
-            description: String::new(),
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Letter frequencies in percent, from large samples of each language.
+const ENGLISH: &[(char, f64)] = &[
+    ('a', 8.167), ('b', 1.492), ('c', 2.782), ('d', 4.253), ('e', 12.702), ('f', 2.228),
+    ('g', 2.015), ('h', 6.094), ('i', 6.966), ('j', 0.153), ('k', 0.772), ('l', 4.025),
+    ('m', 2.406), ('n', 6.749), ('o', 7.507), ('p', 1.929), ('q', 0.095), ('r', 5.987),
+    ('s', 6.327), ('t', 9.056), ('u', 2.758), ('v', 0.978), ('w', 2.360), ('x', 0.150),
+    ('y', 1.974), ('z', 0.074),
+];
+const FRENCH: &[(char, f64)] = &[
+    ('a', 7.636), ('b', 0.901), ('c', 3.260), ('d', 3.669), ('e', 14.715), ('f', 1.066),
+    ('g', 0.866), ('h', 0.737), ('i', 7.529), ('j', 0.613), ('k', 0.074), ('l', 5.456),
+    ('m', 2.968), ('n', 7.095), ('o', 5.796), ('p', 2.521), ('q', 1.362), ('r', 6.693),
+    ('s', 7.948), ('t', 7.244), ('u', 6.311), ('v', 1.838), ('w', 0.049), ('x', 0.427),
+    ('y', 0.128), ('z', 0.326),
+];
+const GERMAN: &[(char, f64)] = &[
+    ('a', 6.516), ('b', 1.886), ('c', 2.732), ('d', 5.076), ('e', 16.396), ('f', 1.656),
+    ('g', 3.009), ('h', 4.577), ('i', 6.550), ('j', 0.268), ('k', 1.417), ('l', 3.437),
+    ('m', 2.534), ('n', 9.776), ('o', 2.594), ('p', 0.670), ('q', 0.018), ('r', 7.003),
+    ('s', 7.270), ('t', 6.154), ('u', 4.166), ('v', 0.846), ('w', 1.921), ('x', 0.034),
+    ('y', 0.039), ('z', 1.134),
+];
+const SPANISH: &[(char, f64)] = &[
+    ('a', 11.525), ('b', 2.215), ('c', 4.019), ('d', 5.010), ('e', 12.181), ('f', 0.692),
+    ('g', 1.768), ('h', 0.703), ('i', 6.247), ('j', 0.493), ('k', 0.011), ('l', 4.967),
+    ('m', 3.157), ('n', 6.712), ('o', 8.683), ('p', 2.510), ('q', 0.877), ('r', 6.871),
+    ('s', 7.977), ('t', 4.632), ('u', 2.927), ('v', 1.138), ('w', 0.017), ('x', 0.215),
+    ('y', 1.008), ('z', 0.467),
+];
+
+/// A named table of how often characters should come up, given either as
+/// weights or as a corpus file to count them in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrequencyTable {
+    pub name: String,
+    /// Relative weight of each character.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub weights: BTreeMap<String, f64>,
+    /// A text file to count characters in instead, relative to the config
+    /// directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corpus: Option<PathBuf>,
+}
+
+impl FrequencyTable {
+    fn builtin(name: &str, weights: &[(char, f64)]) -> Self {
+        Self {
+            name: String::from(name),
+            weights: weights.iter().map(|(c, w)| (c.to_string(), *w)).collect(),
+            corpus: None,
+        }
+    }
+
+    /// The tables that are always available.
+    pub fn builtins() -> Vec<Self> {
+        vec![
+            Self::builtin("english", ENGLISH),
+            Self::builtin("french", FRENCH),
+            Self::builtin("german", GERMAN),
+            Self::builtin("spanish", SPANISH),
+        ]
+    }
+
+    /// Work out the character weights, reading the corpus if there is one.
+    pub fn resolve(&self) -> Result<Frequencies, Error> {
+        let mut counts: BTreeMap<char, f64> = BTreeMap::new();
+        if let Some(path) = &self.corpus {
+            let text = match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(source) => {
+                    return Err(Error::ConfigIo {
+                        path: path.clone(),
+                        source,
+                    })
+                }
+            };
+            for c in text.chars().filter(|c| !c.is_control()).flat_map(char::to_lowercase) {
+                *counts.entry(c).or_insert(0.0) += 1.0;
+            }
+        }
+        for (s, w) in &self.weights {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if *w >= 0.0 && w.is_finite() => {
+                    counts.insert(c, *w);
+                }
+                _ => {
+                    return Err(Error::Config(format!(
+                        "Frequency table `{}` needs a single character and a weight of at least 0 for each entry, not `{}`: {}",
+                        self.name, s, w
+                    )))
+                }
+            }
+        }
+        if !counts.values().any(|w| *w > 0.0) {
+            return Err(Error::Config(format!(
+                "Frequency table `{}` has no characters with any weight",
+                self.name
+            )));
+        }
+        Ok(Frequencies::new(counts))
+    }
+}
+
+/// Relative weights of characters, resolved from a `FrequencyTable`.
+///
+/// Lowercase characters also set the weight of their uppercase forms, unless
+/// those have their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frequencies {
+    weights: BTreeMap<char, f64>,
+    /// Weight of characters that aren't in the table.
+    default: f64,
+}
+
+impl Frequencies {
+    fn new(counts: BTreeMap<char, f64>) -> Self {
+        let default = counts.values().sum::<f64>() / counts.len() as f64;
+        let mut weights = counts.clone();
+        for (c, w) in &counts {
+            let mut upper = c.to_uppercase();
+            if let (Some(u), None) = (upper.next(), upper.next()) {
+                weights.entry(u).or_insert(*w);
+            }
+        }
+        Self { weights, default }
+    }
+
+    /// Every character in the table and its weight, in character order.
+    pub fn iter(&self) -> impl Iterator<Item = (char, f64)> + '_ {
+        self.weights.iter().map(|(c, w)| (*c, *w))
+    }
+
+    /// The weight of `c`, which is the average weight in the table for
+    /// characters that aren't in it.
+    pub fn weight(&self, c: char) -> f64 {
+        self.weights.get(&c).copied().unwrap_or(self.default)
+    }
+
+    /// The weight of characters that aren't in the table.
+    pub fn default_weight(&self) -> f64 {
+        self.default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequencies_test() {
+        let english = FrequencyTable::builtins().remove(0).resolve().unwrap();
+        assert_eq!(english.weight('e'), 12.702);
+        assert_eq!(english.weight('E'), 12.702);
+        assert!((english.weight('7') - 100.0 / 26.0).abs() < 0.01);
+
+        let table = FrequencyTable {
+            name: String::from("bad"),
+            weights: vec![(String::from("ab"), 1.0)].into_iter().collect(),
+            corpus: None,
+        };
+        assert!(table.resolve().is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use super::Alternative;
+use crate::pattern::CompositePattern;
+
+fn default_start() -> String {
+    String::from("start")
+}
+fn default_max_depth() -> u32 {
+    16
+}
+
+/// A grammar rule, which expands to its pattern or one of its weighted
+/// alternatives. `#name#` in a pattern expands another rule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "CompositePattern::is_empty")]
+    pub pattern: CompositePattern,
+    /// Weighted choices used instead of `pattern` when not empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
+}
+
+impl Rule {
+    pub fn new(name: &str, pattern: &str) -> Self {
+        Self {
+            name: String::from(name),
+            pattern: CompositePattern::from(pattern),
+            alternatives: Vec::new(),
+        }
+    }
+
+    pub fn with_alternatives(name: &str, alternatives: &[(&str, f64)]) -> Self {
+        Self {
+            name: String::from(name),
+            pattern: CompositePattern::default(),
+            alternatives: alternatives
+                .iter()
+                .map(|(p, w)| Alternative::new(CompositePattern::from(*p), *w))
+                .collect(),
+        }
+    }
+}
+
+/// A named set of rules that can refer to each other, and to themselves,
+/// to build strings like titles and place names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grammar {
+    pub name: String,
+    /// The rule strings are generated from.
+    #[serde(default = "default_start")]
+    pub start: String,
+    /// Most rules that can be expanded inside each other. Deeper down,
+    /// only alternatives that can finish in time are picked.
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    pub rules: Vec<Rule>,
+}
+
+impl Grammar {
+    pub fn new(name: &str, rules: Vec<Rule>) -> Self {
+        Self {
+            name: String::from(name),
+            start: default_start(),
+            max_depth: default_max_depth(),
+            rules,
+        }
+    }
+
+    /// The grammars that are always available.
+    pub fn builtins() -> Vec<Self> {
+        vec![Self::new(
+            "place",
+            vec![
+                Rule::with_alternatives(
+                    "start",
+                    &[
+                        ("the #adj# #building# of #name#", 3.0),
+                        ("#name#'s #building#", 2.0),
+                        ("#start#, beyond #start#", 0.5),
+                    ],
+                ),
+                Rule::new(
+                    "adj",
+                    "(Sunken|Burning|Silent|Shattered|Hollow|Gilded|Drowned)",
+                ),
+                Rule::new("building", "(Keep|Spire|Hall|Abbey|Citadel|Bastion|Vault)"),
+                Rule::new("name", "[A-Z](ar|el|or|yn|ir|a|e|o)[lnrv](ow|is|en|ath|a)"),
+            ],
+        )]
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What `min_len` and `max_len` count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnit {
+    /// Unicode scalar values.
+    #[default]
+    Chars,
+    /// Extended grapheme clusters, i.e. what a reader sees as one character,
+    /// like `e` followed by a combining accent.
+    Graphemes,
+}
+
+impl LengthUnit {
+    /// The length of `s` in this unit.
+    pub fn measure(&self, s: &str) -> usize {
+        match self {
+            Self::Chars => s.chars().count(),
+            Self::Graphemes => s.graphemes(true).count(),
+        }
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chars" => Ok(Self::Chars),
+            "graphemes" => Ok(Self::Graphemes),
+            _ => Err(format!("Unknown length unit `{}`", s)),
+        }
+    }
+}
+
+impl Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chars => write!(f, "characters"),
+            Self::Graphemes => write!(f, "graphemes"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_test() {
+        let s = "ne\u{301}e";
+        assert_eq!(LengthUnit::Chars.measure(s), 4);
+        assert_eq!(LengthUnit::Graphemes.measure(s), 3);
+    }
+}
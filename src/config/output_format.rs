@@ -1,7 +1,7 @@
 use std::fmt::{self, Display};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub enum OutputFormat {
     Simple,
@@ -18,7 +18,7 @@ impl From<&str> for OutputFormat {
             "table" => Self::Table,
             "json" => Self::Json,
             "csv" => Self::Csv,
-            "simple" | _ => Self::Simple
+            _ => Self::Simple
         }
     }
 }
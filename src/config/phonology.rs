@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+fn default_min_syllables() -> u32 {
+    1
+}
+fn default_max_syllables() -> u32 {
+    3
+}
+
+/// A named sound system that words are built from syllable by syllable:
+/// each syllable is an onset, a nucleus and a coda picked from weighted
+/// inventories, and no word may contain a forbidden sequence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Phonology {
+    pub name: String,
+    /// Sounds that can start a syllable, and their weights. `""` allows
+    /// syllables that start with their nucleus.
+    pub onsets: BTreeMap<String, f64>,
+    /// Sounds at the heart of a syllable, usually vowels.
+    pub nuclei: BTreeMap<String, f64>,
+    /// Sounds that can end a syllable. `""` allows open syllables.
+    pub codas: BTreeMap<String, f64>,
+    /// Sequences no word may contain. `^` at the start only forbids them at
+    /// the start of a word, and `$` at the end only at the end.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden: Vec<String>,
+    #[serde(default = "default_min_syllables")]
+    pub min_syllables: u32,
+    #[serde(default = "default_max_syllables")]
+    pub max_syllables: u32,
+}
+
+fn inventory(entries: &[(&str, f64)]) -> BTreeMap<String, f64> {
+    entries.iter().map(|(s, w)| (String::from(*s), *w)).collect()
+}
+
+impl Phonology {
+    /// The phonologies that are always available.
+    pub fn builtins() -> Vec<Self> {
+        vec![Self {
+            name: String::from("basic"),
+            onsets: inventory(&[
+                ("", 3.0), ("b", 1.0), ("d", 1.5), ("f", 1.0), ("g", 1.0), ("h", 1.0),
+                ("k", 1.5), ("l", 2.0), ("m", 2.0), ("n", 2.0), ("p", 1.0), ("r", 2.0),
+                ("s", 2.0), ("t", 2.0), ("v", 1.0), ("w", 0.5), ("br", 0.5), ("dr", 0.5),
+                ("fl", 0.3), ("gr", 0.5), ("kr", 0.3), ("pl", 0.3), ("st", 0.5), ("th", 1.0),
+                ("sh", 0.5), ("ch", 0.3),
+            ]),
+            nuclei: inventory(&[
+                ("a", 4.0), ("e", 4.0), ("i", 3.0), ("o", 3.0), ("u", 1.5), ("ai", 0.5),
+                ("ea", 0.5), ("ou", 0.3), ("y", 0.3),
+            ]),
+            codas: inventory(&[
+                ("", 6.0), ("n", 2.0), ("r", 1.5), ("l", 1.5), ("s", 1.0), ("t", 0.7),
+                ("m", 0.7), ("nd", 0.3), ("st", 0.3), ("th", 0.3),
+            ]),
+            forbidden: ["aa", "ii", "uu", "yy", "ea$", "ou$", "hh", "thth", "ww", "tht", "sht"]
+                .iter()
+                .map(|s| String::from(*s))
+                .collect(),
+            min_syllables: 2,
+            max_syllables: 3,
+        }]
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Distribution of the number of repetitions an open-ended quantifier
+/// (`*`, `+` or `{n,}`) adds beyond its minimum.
+///
+/// Every distribution is truncated to `0..=cap`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum RepeatDist {
+    Uniform { cap: u32 },
+    Geometric { mean: f64, cap: u32 },
+    Poisson { mean: f64, cap: u32 },
+    Normal { mean: f64, std_dev: f64, cap: u32 },
+}
+
+impl RepeatDist {
+    pub fn cap(&self) -> u32 {
+        match self {
+            Self::Uniform { cap }
+            | Self::Geometric { cap, .. }
+            | Self::Poisson { cap, .. }
+            | Self::Normal { cap, .. } => *cap,
+        }
+    }
+
+    /// Relative weight of each count in `0..=cap`.
+    pub fn weights(&self) -> Vec<f64> {
+        let counts = 0..=self.cap();
+        match *self {
+            Self::Uniform { .. } => counts.map(|_| 1.0).collect(),
+            Self::Geometric { mean, .. } => {
+                let q = mean / (mean + 1.0);
+                counts.map(|k| q.powi(k as i32)).collect()
+            }
+            Self::Poisson { mean, .. } => {
+                // Computed in log space, so large counts don't overflow.
+                let mut ln_fact = 0.0;
+                counts
+                    .map(|k| {
+                        if k > 0 {
+                            ln_fact += (k as f64).ln();
+                        }
+                        (k as f64 * mean.ln() - mean - ln_fact).exp()
+                    })
+                    .collect()
+            }
+            Self::Normal { mean, std_dev, .. } => counts
+                .map(|k| {
+                    let z = (k as f64 - mean) / std_dev;
+                    (-0.5 * z * z).exp()
+                })
+                .collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::Uniform { .. } => Ok(()),
+            Self::Geometric { mean, .. } | Self::Poisson { mean, .. } if mean.is_nan() || mean <= 0.0 => {
+                Err(String::from("The mean must be greater than 0"))
+            }
+            Self::Normal { std_dev, .. } if std_dev.is_nan() || std_dev <= 0.0 => {
+                Err(String::from("The standard deviation must be greater than 0"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for RepeatDist {
+    fn default() -> Self {
+        Self::Geometric { mean: 4.0, cap: 16 }
+    }
+}
+
+/// Parses `uniform[:CAP]`, `geometric[:MEAN[:CAP]]`, `poisson[:MEAN[:CAP]]`
+/// and `normal[:MEAN[:STD_DEV[:CAP]]]`.
+impl FromStr for RepeatDist {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or("");
+        let args = parts
+            .map(|p| p.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| String::from("The distribution parameters must be numbers"))?;
+        let arg = |i: usize, default: f64| args.get(i).copied().unwrap_or(default);
+        let cap = |i: usize| {
+            let cap = arg(i, 16.0);
+            if cap >= 0.0 && cap.fract() == 0.0 && cap <= u32::MAX as f64 {
+                Ok(cap as u32)
+            } else {
+                Err(String::from("The cap must be a non-negative integer"))
+            }
+        };
+        let (dist, max_args) = match name {
+            "uniform" => (Self::Uniform { cap: cap(0)? }, 1),
+            "geometric" => (
+                Self::Geometric {
+                    mean: arg(0, 4.0),
+                    cap: cap(1)?,
+                },
+                2,
+            ),
+            "poisson" => (
+                Self::Poisson {
+                    mean: arg(0, 4.0),
+                    cap: cap(1)?,
+                },
+                2,
+            ),
+            "normal" => (
+                Self::Normal {
+                    mean: arg(0, 4.0),
+                    std_dev: arg(1, 2.0),
+                    cap: cap(2)?,
+                },
+                3,
+            ),
+            _ => return Err(format!("Unknown distribution `{}`", name)),
+        };
+        if args.len() > max_args {
+            return Err(format!("Too many parameters for `{}`", name));
+        }
+        dist.validate()?;
+        Ok(dist)
+    }
+}
+
+impl Display for RepeatDist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uniform { cap } => write!(f, "uniform:{}", cap),
+            Self::Geometric { mean, cap } => write!(f, "geometric:{}:{}", mean, cap),
+            Self::Poisson { mean, cap } => write!(f, "poisson:{}:{}", mean, cap),
+            Self::Normal { mean, std_dev, cap } => {
+                write!(f, "normal:{}:{}:{}", mean, std_dev, cap)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeat_dist_parse_test() {
+        assert_eq!(
+            "geometric".parse::<RepeatDist>(),
+            Ok(RepeatDist::Geometric { mean: 4.0, cap: 16 })
+        );
+        assert_eq!(
+            "normal:6:1.5:10".parse::<RepeatDist>(),
+            Ok(RepeatDist::Normal {
+                mean: 6.0,
+                std_dev: 1.5,
+                cap: 10
+            })
+        );
+        assert_eq!("uniform:100".parse::<RepeatDist>(), Ok(RepeatDist::Uniform { cap: 100 }));
+        assert!("poisson:0".parse::<RepeatDist>().is_err());
+        assert!("uniform:1.5".parse::<RepeatDist>().is_err());
+        assert!("uniform:1:2".parse::<RepeatDist>().is_err());
+        assert!("zipf".parse::<RepeatDist>().is_err());
+        for d in &["uniform:3", "geometric:2.5:8", "poisson:3:12", "normal:5:2:9"] {
+            assert_eq!(d.parse::<RepeatDist>().unwrap().to_string(), *d);
+        }
+    }
+
+    #[test]
+    fn repeat_dist_weights_test() {
+        let w = RepeatDist::Geometric { mean: 1.0, cap: 3 }.weights();
+        assert_eq!(w, vec![1.0, 0.5, 0.25, 0.125]);
+        let w = RepeatDist::Poisson { mean: 2.0, cap: 3 }.weights();
+        assert!((w[1] / w[0] - 2.0).abs() < 1e-9);
+        assert!((w[3] / w[2] - 2.0 / 3.0).abs() < 1e-9);
+        let w = RepeatDist::Normal {
+            mean: 2.0,
+            std_dev: 1.0,
+            cap: 4,
+        }
+        .weights();
+        assert!((w[1] - w[3]).abs() < 1e-12 && w[2] > w[1]);
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// How strings are drawn from a pattern.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// Pick each alternation branch, repetition count and class member in
+    /// turn. Fast, but short alternatives come up far more often than long
+    /// ones: `a|[b-z]{5}` gives "a" half the time.
+    #[default]
+    PerNode,
+    /// Every string the pattern can generate, up to a maximum length, is
+    /// equally likely.
+    Uniform,
+}
+
+impl FromStr for Sampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "per_node" => Ok(Self::PerNode),
+            "uniform" => Ok(Self::Uniform),
+            _ => Err(format!("Unknown sampling mode `{}`", s)),
+        }
+    }
+}
+
+impl Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PerNode => write!(f, "per_node"),
+            Self::Uniform => write!(f, "uniform"),
+        }
+    }
+}
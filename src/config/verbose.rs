@@ -1,7 +1,7 @@
 use std::fmt::{self, Display};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
 #[derive(Serialize, Deserialize)]
 pub enum Verbosity {
     NotVerbose,
@@ -14,7 +14,7 @@ impl From<u8> for Verbosity {
         match verbosity {
             0 => Self::NotVerbose,
             1 => Self::Verbose,
-            2 | _ => Self::VeryVerbose,
+            _ => Self::VeryVerbose,
         }
     }
 }
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use crate::error::Error;
+
+/// The entries of a word list fragment and how likely each one is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordList {
+    pub name: String,
+    pub words: Vec<String>,
+    pub weights: Vec<f64>,
+}
+
+impl WordList {
+    /// Read the word list at `path`.
+    pub fn load(name: &str, path: &Path) -> Result<Self, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(name, &text),
+            Err(source) => Err(Error::DataIo {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    /// Parse a word list with one entry per line, optionally followed by a
+    /// tab and its weight. Blank lines are skipped.
+    pub fn parse(name: &str, text: &str) -> Result<Self, Error> {
+        let mut list = Self {
+            name: String::from(name),
+            words: Vec::new(),
+            weights: Vec::new(),
+        };
+        for (n, line) in text.lines().enumerate() {
+            let (word, weight) = match line.rsplit_once('\t') {
+                Some((word, weight)) => match weight.trim().parse::<f64>() {
+                    Ok(w) if w >= 0.0 && w.is_finite() => (word, w),
+                    _ => {
+                        return Err(Error::Config(format!(
+                            "Word list `{}` needs a weight of at least 0 after the tab on line {}, not `{}`",
+                            name,
+                            n + 1,
+                            weight
+                        )))
+                    }
+                },
+                None => (line, 1.0),
+            };
+            let word = word.trim();
+            if !word.is_empty() {
+                list.words.push(String::from(word));
+                list.weights.push(weight);
+            }
+        }
+        if !list.weights.iter().any(|w| *w > 0.0) {
+            return Err(Error::Config(format!(
+                "Word list `{}` has no words with any weight",
+                name
+            )));
+        }
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_list_test() {
+        let list = WordList::parse("animal", "wolf\n\nred fox\t2.5\n  owl  \n").unwrap();
+        assert_eq!(list.words, vec!["wolf", "red fox", "owl"]);
+        assert_eq!(list.weights, vec![1.0, 2.5, 1.0]);
+        assert!(WordList::parse("bad", "wolf\tmany\n").is_err());
+        assert!(WordList::parse("empty", "\n\n").is_err());
+    }
+}
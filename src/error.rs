@@ -0,0 +1,291 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::config::LengthUnit;
+use crate::generate::stats::LanguageSize;
+
+/// Errors produced while loading configuration, assembling patterns and
+/// generating strings.
+#[derive(Debug)]
+pub enum Error {
+    /// The assembled pattern is not a valid regular expression.
+    ///
+    /// `span` is the offending part of `pattern`, if known.
+    Parse {
+        pattern: String,
+        span: Option<Range<usize>>,
+        source: Box<regex_syntax::Error>,
+    },
+    /// The pattern misuses one of String Studio's syntax extensions.
+    Extension {
+        pattern: String,
+        span: Range<usize>,
+        message: String,
+    },
+    /// The pattern parsed, but contains a construct strings can't be generated from.
+    Unsupported { pattern: String, kind: &'static str },
+    /// No generated string satisfied the pattern's anchors and word boundaries.
+    Unsatisfiable { pattern: String },
+    /// No string within the length bounds could be generated.
+    Length {
+        pattern: String,
+        min_len: Option<usize>,
+        max_len: Option<usize>,
+        unit: LengthUnit,
+    },
+    /// The pattern can generate arbitrarily long strings, and no maximum
+    /// length was given.
+    Unbounded { pattern: String },
+    /// The pattern is too large to analyze.
+    TooComplex { pattern: String },
+    /// Analyzing the pattern went past a limit on its automaton's size or
+    /// the time it may take, described by `limit`.
+    AnalysisLimit { pattern: String, limit: String },
+    /// `--unique` ran out of retries before finding enough distinct strings.
+    ///
+    /// `possible` is how many distinct strings the pattern can generate, if
+    /// it could be worked out.
+    NotEnoughUnique {
+        requested: u32,
+        found: usize,
+        retries: u32,
+        possible: Option<LanguageSize>,
+    },
+    /// Ran out of retries before finding enough strings unlike each other
+    /// and the existing names.
+    NotDiverse {
+        requested: u32,
+        found: usize,
+        retries: u32,
+    },
+    /// A `@name@` reference names a fragment that isn't defined.
+    ///
+    /// `pattern` is the pattern assembled up to and including the reference.
+    UnknownFragment {
+        pattern: String,
+        name: String,
+        span: Range<usize>,
+    },
+    /// Reading or writing a config file failed.
+    ConfigIo { path: PathBuf, source: io::Error },
+    /// A config file could not be (de)serialized.
+    ConfigJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// Reading or writing a data file, like a word list or model, failed.
+    DataIo { path: PathBuf, source: io::Error },
+    /// A model file could not be (de)serialized.
+    ModelJson {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// Every word started from a phonology ran into a forbidden sequence.
+    Forbidden { phonology: String },
+    /// Every word generated from a model was one of its training words.
+    NotNovel { model: String, attempts: u32 },
+    /// No string generated scored at least `min_score` for pronounceability.
+    LowScore {
+        pattern: String,
+        min_score: f64,
+        attempts: u32,
+    },
+    /// Every string generated contained a blocked word.
+    Blocked { pattern: String, attempts: u32 },
+    /// A backend drew a string it had to throw away, and drawing again may
+    /// do better. Holds the error to report if every draw misses.
+    Miss(Box<Error>),
+    /// A config value is out of range.
+    Config(String),
+    /// Invalid command line arguments.
+    Args(String),
+    /// Writing the generated strings failed.
+    Output(String),
+}
+
+impl Error {
+    /// Wrap a `regex_syntax` error, mapping its span through `map_span`.
+    pub fn parse(
+        pattern: &str,
+        source: regex_syntax::Error,
+        map_span: impl Fn(Range<usize>) -> Range<usize>,
+    ) -> Self {
+        let span = match &source {
+            regex_syntax::Error::Parse(e) => Some(e.span()),
+            regex_syntax::Error::Translate(e) => Some(e.span()),
+            _ => None,
+        };
+        Self::Parse {
+            pattern: String::from(pattern),
+            span: span.map(|s| map_span(s.start.offset..s.end.offset)),
+            source: Box::new(source),
+        }
+    }
+
+    /// The pattern the error occurred in, and the byte range of the offending
+    /// part of it, if known.
+    pub fn location(&self) -> Option<(&str, Range<usize>)> {
+        match self {
+            Self::Parse {
+                pattern,
+                span: Some(span),
+                ..
+            } => Some((pattern, span.clone())),
+            Self::Extension { pattern, span, .. } => Some((pattern, span.clone())),
+            Self::UnknownFragment { pattern, span, .. } => Some((pattern, span.clone())),
+            Self::Miss(error) => error.location(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse { source, .. } => match source.as_ref() {
+                regex_syntax::Error::Parse(e) => write!(f, "Failed to parse pattern: {}", e.kind()),
+                regex_syntax::Error::Translate(e) => {
+                    write!(f, "Failed to parse pattern: {}", e.kind())
+                }
+                e => write!(f, "Failed to parse pattern: {}", e),
+            },
+            Self::Extension { message, .. } => write!(f, "Invalid pattern extension: {}", message),
+            Self::Unsupported { pattern, kind } => write!(
+                f,
+                "Can't generate strings from {} in pattern `{}`",
+                kind, pattern
+            ),
+            Self::Unsatisfiable { pattern } => write!(
+                f,
+                "Failed to generate a string satisfying the anchors and word boundaries in pattern `{}`",
+                pattern
+            ),
+            Self::Length {
+                pattern,
+                min_len,
+                max_len,
+                unit,
+            } => {
+                let range = match (min_len, max_len) {
+                    (Some(min), Some(max)) => format!("{} to {}", min, max),
+                    (Some(min), None) => format!("at least {}", min),
+                    (None, Some(max)) => format!("at most {}", max),
+                    (None, None) => String::from("any"),
+                };
+                write!(
+                    f,
+                    "Failed to generate a string of {} {} from pattern `{}`",
+                    range, unit, pattern
+                )
+            }
+            Self::Unbounded { pattern } => write!(
+                f,
+                "Pattern `{}` can generate strings of any length; set a maximum length",
+                pattern
+            ),
+            Self::TooComplex { pattern } => write!(f, "Pattern `{}` is too complex to analyze", pattern),
+            Self::AnalysisLimit { pattern, limit } => write!(
+                f,
+                "Gave up analyzing pattern `{}` after {}; try a simpler pattern or a lower maximum length",
+                pattern, limit
+            ),
+            Self::NotEnoughUnique {
+                requested,
+                found,
+                retries,
+                possible,
+            } => {
+                write!(f, "Only found {} ", found)?;
+                if *requested > 0 {
+                    write!(f, "of {} ", requested)?;
+                }
+                write!(
+                    f,
+                    "unique strings, giving up after {} duplicates in a row",
+                    retries
+                )?;
+                match possible {
+                    Some(LanguageSize::Exact(n)) => {
+                        write!(f, "; the pattern can only generate {} distinct strings", n)
+                    }
+                    Some(LanguageSize::AtMost(n)) => {
+                        write!(f, "; the pattern can generate at most {} distinct strings", n)
+                    }
+                    Some(LanguageSize::Unbounded) => Ok(()),
+                    None => write!(f, "; how many distinct strings the pattern can generate is unknown"),
+                }
+            }
+            Self::NotDiverse {
+                requested,
+                found,
+                retries,
+            } => {
+                write!(f, "Only found {} ", found)?;
+                if *requested > 0 {
+                    write!(f, "of {} ", requested)?;
+                }
+                write!(
+                    f,
+                    "strings unlike each other, giving up after {} near-duplicates in a row; try a lower `--min-distance` or leaving out `--phonetic`",
+                    retries
+                )
+            }
+            Self::UnknownFragment { name, .. } => write!(f, "Unknown fragment `{}`", name),
+            Self::ConfigIo { path, source } => {
+                write!(f, "Failed to access config file `{}`: {}", path.display(), source)
+            }
+            Self::ConfigJson { path, source } => {
+                write!(f, "Invalid config file `{}`: {}", path.display(), source)
+            }
+            Self::DataIo { path, source } => {
+                write!(f, "Failed to access `{}`: {}", path.display(), source)
+            }
+            Self::ModelJson { path, source } => {
+                write!(f, "Invalid model file `{}`: {}", path.display(), source)
+            }
+            Self::Forbidden { phonology } => write!(
+                f,
+                "Failed to build a word from phonology `{}` without a forbidden sequence",
+                phonology
+            ),
+            Self::NotNovel { model, attempts } => write!(
+                f,
+                "All of {} words generated from model `{}` were in its training set; try a lower order",
+                attempts, model
+            ),
+            Self::LowScore {
+                pattern,
+                min_score,
+                attempts,
+            } => write!(
+                f,
+                "None of {} strings generated from `{}` had a pronounceability score of at least {}; try a lower `--min-score`",
+                attempts, pattern, min_score
+            ),
+            Self::Blocked { pattern, attempts } => write!(
+                f,
+                "All of {} strings generated from `{}` contained a blocked word",
+                attempts, pattern
+            ),
+            Self::Miss(error) => error.fmt(f),
+            Self::Config(msg) => write!(f, "Invalid configuration: {}", msg),
+            Self::Args(msg) => write!(f, "{}", msg),
+            Self::Output(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse { source, .. } => Some(source.as_ref()),
+            Self::ConfigIo { source, .. } => Some(source),
+            Self::ConfigJson { source, .. } => Some(source),
+            Self::DataIo { source, .. } => Some(source),
+            Self::ModelJson { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
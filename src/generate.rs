@@ -1,11 +1,41 @@
+pub mod alias;
+pub mod backend;
+pub mod blocklist;
+pub mod diversity;
+mod dfa;
+pub mod enumerate;
+pub mod grammar;
+pub mod markov;
+pub mod phonology;
+pub(crate) mod extension;
+mod node;
+mod program;
 pub mod regex_gen;
+pub mod score;
+pub mod stats;
+mod uniform;
 
-use crate::config::Config;
+use crate::config::{eprintln_v1, Config, LengthUnit, Sampling};
+use crate::pattern::{CompositePattern, Sources};
+use crate::error::Error;
+
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::thread;
 
 use rand::prelude::*;
 use rand_xoshiro::Xoshiro512StarStar;
 
-use self::regex_gen::RegexGen;
+use self::backend::{
+    Generator, DEFAULT_BACKEND, GRAMMAR_BACKEND, MARKOV_BACKEND, MAX_ATTEMPTS, PHONOLOGY_BACKEND,
+};
+use self::blocklist::Blocklist;
+use self::diversity::{Conflict, Diversity};
+use self::markov::MarkovModel;
+use self::regex_gen::{Record, RegexGen, RegexGenOptions, Source};
+use self::score::Scorer;
+use self::stats::Stats;
 
 fn apply_xforms(config: &Config, s: String) -> String {
     let mut s = s;
@@ -15,24 +45,800 @@ fn apply_xforms(config: &Config, s: String) -> String {
     s
 }
 
-pub fn generate(config: &Config) -> Result<Vec<String>, String> {
-    let pat = config.pattern.assemble_pattern(&config.fragments)?;
-    let mut rng = if config.seed == 0 {
+/// Whether `s` is within the configured length bounds.
+fn fits_length(config: &Config, s: &str) -> bool {
+    if config.min_len.is_none() && config.max_len.is_none() {
+        return true;
+    }
+    let len = config.length_unit.measure(s);
+    config.min_len.map_or(true, |min| len >= min) && config.max_len.map_or(true, |max| len <= max)
+}
+
+/// The length bounds, in characters before transforms, for a generator to
+/// steer towards.
+fn length_hints(config: &Config) -> (Option<usize>, Option<usize>) {
+    // Transforms never make a string shorter, and a grapheme is at least one
+    // character, so only the bounds that imply the configured ones are
+    // passed on. Uniform sampling needs a maximum either way.
+    let uniform = config.sampling == Sampling::Uniform;
+    match config.length_unit {
+        _ if uniform => (config.min_len, config.max_len),
+        LengthUnit::Chars if config.xforms.is_empty() => (config.min_len, config.max_len),
+        LengthUnit::Chars => (None, config.max_len),
+        LengthUnit::Graphemes if config.xforms.is_empty() => (config.min_len, None),
+        LengthUnit::Graphemes => (None, None),
+    }
+}
+
+/// Options for generating from the configured pattern, without the word
+/// lists and other backends it uses.
+fn regex_gen_options(config: &Config) -> Result<RegexGenOptions, Error> {
+    let (min_len, max_len) = length_hints(config);
+    Ok(RegexGenOptions {
+        repeat: config.repeat.clone(),
+        sampling: config.sampling,
+        min_len,
+        max_len,
+        frequencies: config.frequencies()?,
+        charset: config.charset()?,
+        word_lists: Vec::new(),
+        rules: Vec::new(),
+        sources: Vec::new(),
+    })
+}
+
+/// Options for generating from `pattern`, assembled from `config`'s
+/// fragments, with the word lists and other backends it uses.
+fn assemble_options(
+    config: &Config,
+    pattern: &CompositePattern,
+    options: RegexGenOptions,
+) -> Result<(String, RegexGenOptions), Error> {
+    let mut sources = Sources {
+        dir: config.config_dir.clone(),
+        ..Default::default()
+    };
+    let pat = pattern.assemble(&config.fragments, &mut sources)?;
+    let options = RegexGenOptions {
+        word_lists: sources.word_lists,
+        sources: fragment_sources(config, &sources.backends)?,
+        ..options
+    };
+    Ok((pat, options))
+}
+
+/// Generators for the fragments called `names`, each with the backend it
+/// picks and the rest of `config`.
+fn fragment_sources(config: &Config, names: &[String]) -> Result<Vec<Source>, Error> {
+    let mut sources = Vec::with_capacity(names.len());
+    for name in names {
+        let mut config = config.clone();
+        let backend = match config.fragments.iter_mut().find(|f| &f.name == name) {
+            Some(f) => f.backend.take(),
+            None => None,
+        };
+        // The fragment is generated as a pattern of its own, for backends
+        // that read one, and only the whole string is transformed and held
+        // to the length bounds.
+        config.pattern = CompositePattern::from(format!("@{}@", name).as_str());
+        config.backend = backend;
+        config.min_len = None;
+        config.max_len = None;
+        config.xforms.clear();
+        let gen = generator(&config)?;
+        sources.push(Source {
+            name: name.clone(),
+            gen: Arc::from(gen),
+        });
+    }
+    Ok(sources)
+}
+
+/// The regex backend, for the configured pattern.
+fn regex_generator(config: &Config) -> Result<Box<dyn Generator>, Error> {
+    let (pat, options) = assemble_options(config, &config.pattern, regex_gen_options(config)?)?;
+    Ok(Box::new(RegexGen::with_options(&pat, &options)?))
+}
+
+/// The name of the configured backend: the Markov one if there's a model,
+/// the grammar one if there's a grammar, the phonology one if there's a
+/// phonology, and the regex one otherwise.
+fn backend_name(config: &Config) -> &str {
+    if let Some(name) = &config.backend {
+        name
+    } else if config.model.is_some() {
+        MARKOV_BACKEND
+    } else if config.grammar.is_some() {
+        GRAMMAR_BACKEND
+    } else if config.phonology.is_some() {
+        PHONOLOGY_BACKEND
+    } else {
+        DEFAULT_BACKEND
+    }
+}
+
+/// Build the generator for the configured backend.
+pub fn generator(config: &Config) -> Result<Box<dyn Generator>, Error> {
+    config.backends.build(backend_name(config), config)
+}
+
+/// What generated strings are checked with besides their length.
+struct Filters {
+    /// Rates strings, if they need rating.
+    scorer: Option<Scorer>,
+    /// Words strings may not contain, if any are blocked.
+    blocklist: Option<Blocklist>,
+}
+
+impl Filters {
+    fn new(config: &Config) -> Result<Self, Error> {
+        Ok(Self {
+            scorer: Self::scorer(config)?,
+            blocklist: Self::blocklist(config)?,
+        })
+    }
+
+    fn scorer(config: &Config) -> Result<Option<Scorer>, Error> {
+        if let Some(min) = config.min_score.filter(|n| !(0.0..=1.0).contains(n)) {
+            return Err(Error::Config(format!(
+                "`min_score` needs to be from 0 to 1, not {}",
+                min
+            )));
+        }
+        if !config.score && config.min_score.is_none() && config.rank.is_none() {
+            return Ok(None);
+        }
+        let model = match &config.score_model {
+            Some(path) => Some(MarkovModel::load(&config.resolve_path(path))?),
+            None => None,
+        };
+        Scorer::new(model.as_ref()).map(Some)
+    }
+
+    fn blocklist(config: &Config) -> Result<Option<Blocklist>, Error> {
+        if !config.blocklist {
+            return Ok(None);
+        }
+        let mut blocklist = Blocklist::builtin();
+        for path in &config.blocklists {
+            blocklist.extend_from_file(&config.resolve_path(path))?;
+        }
+        Ok(Some(blocklist))
+    }
+}
+
+/// Fail unless the configured backend is the regex one, which is the only
+/// one `what` works with.
+fn require_regex_backend(config: &Config, what: &str) -> Result<(), Error> {
+    match backend_name(config) {
+        DEFAULT_BACKEND => Ok(()),
+        name => Err(Error::Config(format!(
+            "{} only works with the `{}` backend, not `{}`",
+            what, DEFAULT_BACKEND, name
+        ))),
+    }
+}
+
+/// Generate a record, transform its string and fields, and check the string
+/// against the length bounds and `filters`, drawing again after a miss or a
+/// string that fails them.
+fn next_record(
+    config: &Config,
+    gen: &dyn Generator,
+    filters: &Filters,
+    rng: &mut dyn RngCore,
+) -> Result<Record, Error> {
+    let (mut misses, mut off_length, mut blocked, mut low_scores) = (0, 0, 0, 0);
+    let mut last_miss = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let record = match gen.generate_record(rng) {
+            Err(Error::Miss(error)) => {
+                misses += 1;
+                last_miss = Some(*error);
+                continue;
+            }
+            record => record?,
+        };
+        let text = apply_xforms(config, record.text);
+        if !fits_length(config, &text) {
+            off_length += 1;
+            continue;
+        }
+        if let Some(word) = filters.blocklist.as_ref().and_then(|b| b.find(&text)) {
+            eprintln_v1(
+                config,
+                format!("Rejected `{}`: it contains the blocked word `{}`", text, word).as_str(),
+            );
+            blocked += 1;
+            continue;
+        }
+        let score = filters.scorer.as_ref().map(|scorer| scorer.score(&text));
+        if score.zip(config.min_score).is_some_and(|(score, min)| score < min) {
+            low_scores += 1;
+            continue;
+        }
+        let fields = record
+            .fields
+            .into_iter()
+            .map(|(name, s)| (name, s.map(|s| apply_xforms(config, s))))
+            .collect();
+        return Ok(Record {
+            text,
+            fields,
+            score,
+        });
+    }
+    // Report whatever threw away the most strings.
+    if let Some(error) = last_miss.filter(|_| misses > off_length.max(blocked).max(low_scores)) {
+        return Err(error);
+    }
+    if blocked > low_scores {
+        return Err(Error::Blocked {
+            pattern: String::from(gen.pattern()),
+            attempts: MAX_ATTEMPTS,
+        });
+    }
+    if let (Some(min_score), true) = (config.min_score, low_scores > 0) {
+        return Err(Error::LowScore {
+            pattern: String::from(gen.pattern()),
+            min_score,
+            attempts: MAX_ATTEMPTS,
+        });
+    }
+    Err(Error::Length {
+        pattern: String::from(gen.pattern()),
+        min_len: config.min_len,
+        max_len: config.max_len,
+        unit: config.length_unit,
+    })
+}
+
+/// Generate the record for one item from its own seed.
+fn seeded_record(
+    config: &Config,
+    gen: &dyn Generator,
+    filters: &Filters,
+    seed: u64,
+) -> Result<Record, Error> {
+    next_record(config, gen, filters, &mut Xoshiro512StarStar::seed_from_u64(seed))
+}
+
+/// How many strings each thread generates at a time when running several.
+const JOB_BATCH: usize = 1024;
+
+/// Lazily generated records for a `Config`, transformed and filtered as
+/// configured. Yields `config.number` records, or never stops if that is 0.
+///
+/// Every string is generated from its own seed, drawn in turn from a master
+/// generator, so the strings come out the same however many threads
+/// `config.jobs` spreads them over.
+///
+/// After an error, the iterator ends.
+pub struct GenerateIter<'a> {
+    config: &'a Config,
+    gen: Box<dyn Generator>,
+    filters: Filters,
+    seeds: Xoshiro512StarStar,
+    jobs: usize,
+    /// Strings generated ahead on other threads, in order.
+    pending: VecDeque<Result<Record, Error>>,
+    /// Strings left to yield, or `None` for no limit.
+    remaining: Option<u32>,
+    seen: HashSet<String>,
+    /// Strings output so far and existing names, if new strings have to be
+    /// unlike them.
+    diversity: Option<Diversity>,
+}
+
+impl GenerateIter<'_> {
+    /// Generate the next batch of strings, split evenly across the jobs.
+    fn fill(&mut self) {
+        // Any string still to yield needs at least one, plus the one being
+        // yielded now.
+        let count = self
+            .remaining
+            .map_or(usize::MAX, |n| n as usize + 1)
+            .min(JOB_BATCH * self.jobs);
+        let seeds: Vec<u64> = (0..count).map(|_| self.seeds.next_u64()).collect();
+        let (config, gen, filters) = (self.config, &*self.gen, &self.filters);
+        let batches: Vec<Vec<Result<Record, Error>>> = thread::scope(|scope| {
+            let handles: Vec<_> = seeds
+                .chunks(count.div_ceil(self.jobs))
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|seed| seeded_record(config, gen, filters, *seed))
+                            .collect()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("generator thread panicked"))
+                .collect()
+        });
+        self.pending.extend(batches.into_iter().flatten());
+    }
+
+    fn next_record(&mut self) -> Result<Record, Error> {
+        if self.jobs == 1 {
+            let seed = self.seeds.next_u64();
+            return seeded_record(self.config, &*self.gen, &self.filters, seed);
+        }
+        if self.pending.is_empty() {
+            self.fill();
+        }
+        self.pending.pop_front().unwrap()
+    }
+
+    /// The way `s` is too much like a string already output, if any.
+    fn conflict(&self, s: &str) -> Option<Conflict> {
+        self.diversity.as_ref().and_then(|d| d.conflict(s))
+    }
+
+    fn next_unique(&mut self) -> Result<Record, Error> {
+        let mut r = self.next_record()?;
+        if self.config.unique || self.diversity.is_some() {
+            let mut retries = 0;
+            loop {
+                let duplicate = self.config.unique && self.seen.contains(&r.text);
+                let conflict = if duplicate { None } else { self.conflict(&r.text) };
+                if !duplicate && conflict.is_none() {
+                    break;
+                }
+                if retries == self.config.unique_retries {
+                    return Err(if duplicate {
+                        Error::NotEnoughUnique {
+                            requested: self.config.number,
+                            found: self.seen.len(),
+                            retries: self.config.unique_retries,
+                            possible: self.gen.size(self.config.max_len),
+                        }
+                    } else {
+                        Error::NotDiverse {
+                            requested: self.config.number,
+                            found: self.seen.len(),
+                            retries: self.config.unique_retries,
+                        }
+                    });
+                }
+                if let Some(conflict) = conflict {
+                    eprintln_v1(
+                        self.config,
+                        format!("Rejected `{}`: {}", r.text, conflict).as_str(),
+                    );
+                }
+                retries += 1;
+                r = self.next_record()?;
+            }
+            self.seen.insert(r.text.clone());
+            if let Some(diversity) = &mut self.diversity {
+                diversity.accept(&r.text);
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl Iterator for GenerateIter<'_> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.remaining {
+            Some(0) => return None,
+            Some(ref mut n) => *n -= 1,
+            None => {}
+        }
+        let result = self.next_unique();
+        if result.is_err() {
+            self.remaining = Some(0);
+        }
+        Some(result)
+    }
+}
+
+/// The strings new ones have to be unlike, if any of the diversity options
+/// are set.
+fn diversity(config: &Config) -> Result<Option<Diversity>, Error> {
+    if config.min_distance.is_none() && !config.phonetic && config.existing_names.is_none() {
+        return Ok(None);
+    }
+    // Without a minimum distance, identical strings still count as too
+    // close, even to `phonetic` alone, which can't key strings with no
+    // letters.
+    let mut diversity = Diversity::new(config.min_distance.unwrap_or(1), config.phonetic);
+    if let Some(path) = &config.existing_names {
+        diversity.accept_file(&config.resolve_path(path))?;
+    }
+    Ok(Some(diversity))
+}
+
+/// Generate records for `config` lazily.
+pub fn generate_iter(config: &Config) -> Result<GenerateIter<'_>, Error> {
+    let seeds = if config.seed == 0 {
         Xoshiro512StarStar::from_entropy()
     } else {
         Xoshiro512StarStar::seed_from_u64(config.seed)
     };
-    if let Some(gen) = RegexGen::new(&pat) {
-        let mut strings: Vec<String> = Vec::new();
+    let jobs = match config.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    Ok(GenerateIter {
+        config,
+        gen: generator(config)?,
+        filters: Filters::new(config)?,
+        seeds,
+        jobs,
+        pending: VecDeque::new(),
+        remaining: if config.number == 0 {
+            None
+        } else {
+            Some(config.number)
+        },
+        seen: HashSet::new(),
+        diversity: diversity(config)?,
+    })
+}
+
+/// Generate `config.number` strings, on `config.jobs` threads. Never returns
+/// if that is 0; use `generate_iter` instead.
+pub fn generate(config: &Config) -> Result<Vec<String>, Error> {
+    generate_iter(config)?.map(|r| r.map(|r| r.text)).collect()
+}
+
+/// Generate `config.number` records, with the text of each named group.
+pub fn generate_records(config: &Config) -> Result<Vec<Record>, Error> {
+    generate_iter(config)?.collect()
+}
+
+/// Generate `config.number` records and keep the `config.rank` most
+/// pronounceable, best first. Strings that score the same stay in the order
+/// they were generated in.
+pub fn generate_ranked(config: &Config) -> Result<Vec<Record>, Error> {
+    let keep = match config.rank {
+        Some(keep) => keep as usize,
+        None => return generate_records(config),
+    };
+    if config.number == 0 {
+        return Err(Error::Config(String::from(
+            "Ranking needs a `number` of strings to pick from other than 0",
+        )));
+    }
+    let mut records = generate_records(config)?;
+    records.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    records.truncate(keep);
+    Ok(records)
+}
 
-        for _ in 0..config.number {
-            match gen.randomize(&mut rng) {
-                Ok(s) => strings.push(apply_xforms(config, s)),
-                Err(_) => return Err(String::from("Failed to generate string!")),
+/// Every string the configured pattern can generate within the length
+/// bounds, lazily and in shortlex order.
+pub fn enumerate_iter(config: &Config) -> Result<impl Iterator<Item = String> + '_, Error> {
+    require_regex_backend(config, "Listing every string")?;
+    // Enumeration needs a maximum even where it's only approximate.
+    let options = RegexGenOptions {
+        max_len: config.max_len,
+        ..regex_gen_options(config)?
+    };
+    let (pat, options) = assemble_options(config, &config.pattern, options)?;
+    let gen = RegexGen::with_options(&pat, &options)?;
+    Ok(gen
+        .enumerate(options.max_len)?
+        .map(move |s| apply_xforms(config, s))
+        .filter(move |s| fits_length(config, s)))
+}
+
+/// Every string the configured pattern can generate within the length
+/// bounds, in shortlex order.
+pub fn enumerate(config: &Config) -> Result<Vec<String>, Error> {
+    Ok(enumerate_iter(config)?.collect())
+}
+
+/// Size and sampling statistics for the configured pattern.
+pub fn stats(config: &Config) -> Result<Stats, Error> {
+    require_regex_backend(config, "Pattern statistics")?;
+    let uniform = config.sampling == Sampling::Uniform;
+    let options = RegexGenOptions {
+        repeat: config.repeat.clone(),
+        sampling: config.sampling,
+        // Uniform sampling needs the maximum to build its sampler.
+        max_len: config.max_len.filter(|_| uniform),
+        frequencies: config.frequencies()?,
+        charset: config.charset()?,
+        ..Default::default()
+    };
+    let (pat, options) = assemble_options(config, &config.pattern, options)?;
+    RegexGen::with_options(&pat, &options)?.stats(config.max_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CompositePattern, Fragment, Xform};
+    use crate::generate::stats::LanguageSize;
+    use std::path::PathBuf;
+
+    #[test]
+    fn unique_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("[a-c]"),
+            number: 3,
+            seed: 7,
+            unique: true,
+            ..Default::default()
+        };
+        let mut strings = generate(&config).unwrap();
+        strings.sort();
+        assert_eq!(strings, vec!["a", "b", "c"]);
+
+        config.number = 4;
+        match generate(&config) {
+            Err(Error::NotEnoughUnique {
+                found: 3,
+                possible: Some(LanguageSize::Exact(n)),
+                ..
+            }) => assert_eq!(n.to_string(), "3"),
+            r => panic!("expected NotEnoughUnique, got {:?}", r),
+        }
+
+        // Counting isn't worth a big automaton just for the error.
+        config.pattern = CompositePattern::from("x{2500}");
+        config.number = 2;
+        match generate(&config) {
+            Err(e @ Error::NotEnoughUnique { possible: None, .. }) => {
+                assert!(e.to_string().ends_with("is unknown"), "{}", e)
             }
+            r => panic!("expected NotEnoughUnique, got {:?}", r),
         }
-        Ok(strings)
-    } else {
-        Err(String::from("Failed to parse pattern!"))
+    }
+
+    #[test]
+    fn generate_iter_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("q[a-z]"),
+            number: 0,
+            seed: 9,
+            xforms: vec![Xform::UAfterQ],
+            ..Default::default()
+        };
+        let strings: Vec<String> = generate_iter(&config)
+            .unwrap()
+            .take(1000)
+            .map(|r| r.unwrap().text)
+            .collect();
+        assert_eq!(strings.len(), 1000);
+        assert!(strings.iter().all(|s| s.starts_with("qu")));
+
+        config.number = 20;
+        assert_eq!(generate_iter(&config).unwrap().count(), 20);
+        assert_eq!(generate(&config).unwrap(), strings[..20]);
+    }
+
+    #[test]
+    fn records_test() {
+        let config = Config {
+            pattern: CompositePattern::from(r"(?P<given>q[a-c])(?P<extra>!)? (?P<family>[x-z]{3})"),
+            number: 50,
+            seed: 4,
+            xforms: vec![Xform::UAfterQ],
+            ..Default::default()
+        };
+        for record in generate_records(&config).unwrap() {
+            let given = record.field("given").unwrap();
+            let family = record.field("family").unwrap();
+            assert!(given.starts_with("qu"));
+            let extra = record.field("extra").unwrap_or("");
+            assert_eq!(record.text, format!("{}{} {}", given, extra, family));
+        }
+        let records = generate_records(&config).unwrap();
+        let names: Vec<&str> = records[0].fields.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["given", "extra", "family"]);
+    }
+
+    #[test]
+    fn jobs_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("[a-z]{2}[0-9]?"),
+            number: 3000,
+            seed: 11,
+            ..Default::default()
+        };
+        let sequential = generate(&config).unwrap();
+        config.jobs = 3;
+        assert_eq!(generate(&config).unwrap(), sequential);
+
+        config.unique = true;
+        config.number = 600;
+        let parallel = generate(&config).unwrap();
+        config.jobs = 1;
+        assert_eq!(generate(&config).unwrap(), parallel);
+    }
+
+    #[test]
+    fn length_after_xforms_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("q?[a-c]{0,3}"),
+            number: 50,
+            seed: 5,
+            xforms: vec![Xform::UAfterQ],
+            min_len: Some(2),
+            max_len: Some(3),
+            ..Default::default()
+        };
+        for s in generate(&config).unwrap() {
+            assert!((2..=3).contains(&s.chars().count()), "{:?}", s);
+        }
+
+        config.pattern = CompositePattern::from("e\\u{301}{1,4}");
+        config.xforms.clear();
+        config.length_unit = LengthUnit::Graphemes;
+        config.min_len = None;
+        config.max_len = Some(1);
+        assert_eq!(generate(&config).unwrap().len(), 50);
+        config.max_len = Some(0);
+        assert!(matches!(generate(&config), Err(Error::Length { .. })));
+    }
+
+    #[test]
+    fn word_list_test() {
+        let path = std::env::temp_dir().join("string_studio_word_list_test.txt");
+        std::fs::write(&path, "Ash\nBrook\n").unwrap();
+        let missing = std::env::temp_dir().join("string_studio_no_such_list.txt");
+        let mut config = Config {
+            pattern: CompositePattern::from(&["@tree@", "ford"] as &[&str]),
+            number: 20,
+            seed: 3,
+            fragments: vec![
+                Fragment {
+                    name: String::from("tree"),
+                    // Relative to the directory of the loaded config file.
+                    wordlist: Some(PathBuf::from("string_studio_word_list_test.txt")),
+                    ..Default::default()
+                },
+                Fragment {
+                    name: String::from("missing"),
+                    wordlist: Some(missing),
+                    ..Default::default()
+                },
+            ],
+            config_dir: Some(std::env::temp_dir()),
+            ..Default::default()
+        };
+        // A list only gets read if the pattern uses it.
+        for s in generate(&config).unwrap() {
+            assert!(s == "Ashford" || s == "Brookford", "{}", s);
+        }
+        config.pattern = CompositePattern::from(&["@missing@"] as &[&str]);
+        assert!(matches!(generate(&config), Err(Error::DataIo { .. })));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fragment_backend_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from(&["@word@", "-[0-9]{2}"] as &[&str]),
+            number: 30,
+            seed: 4,
+            phonology: Some(String::from("basic")),
+            backend: Some(String::from(backend::DEFAULT_BACKEND)),
+            fragments: vec![Fragment {
+                name: String::from("word"),
+                backend: Some(String::from(backend::PHONOLOGY_BACKEND)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let strings = generate(&config).unwrap();
+        for s in &strings {
+            let (word, digits) = s.split_at(s.len() - 3);
+            assert!(!word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase()), "{}", s);
+            assert!(digits.starts_with('-') && digits[1..].chars().all(|c| c.is_ascii_digit()));
+        }
+        let words: HashSet<&str> = strings.iter().map(|s| &s[..s.len() - 3]).collect();
+        assert!(words.len() > 1);
+
+        // The other backend's strings can't be analyzed.
+        assert!(matches!(stats(&config), Err(Error::Unsupported { .. })));
+        assert!(matches!(enumerate(&config), Err(Error::Unsupported { .. })));
+        config.fragments[0].backend = Some(String::from("nope"));
+        assert!(matches!(generate(&config), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn blocklist_test() {
+        let path = std::env::temp_dir().join("string_studio_blocklist_test.txt");
+        std::fs::write(&path, "# Checked after transforms\nQu1t\n").unwrap();
+        let mut config = Config {
+            pattern: CompositePattern::from("q(a|i)t"),
+            number: 30,
+            seed: 2,
+            xforms: vec![Xform::UAfterQ],
+            blocklist: true,
+            blocklists: vec![path.clone()],
+            ..Default::default()
+        };
+        assert!(generate(&config).unwrap().iter().all(|s| s == "quat"));
+
+        config.pattern = CompositePattern::from("(sh|5h)[i!]t");
+        assert!(matches!(generate(&config), Err(Error::Blocked { .. })));
+        config.blocklist = false;
+        assert_eq!(generate(&config).unwrap().len(), 30);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn diversity_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("[KC]al[aou]r[a-c]?"),
+            number: 3,
+            seed: 6,
+            min_distance: Some(2),
+            ..Default::default()
+        };
+        let strings = generate(&config).unwrap();
+        for (i, a) in strings.iter().enumerate() {
+            for b in &strings[i + 1..] {
+                let distance = diversity::edit_distance(&a.to_lowercase(), &b.to_lowercase());
+                assert!(distance >= 2, "{} {}", a, b);
+            }
+        }
+
+        config.min_distance = None;
+        config.phonetic = true;
+        config.pattern = CompositePattern::from("[KC]al[aou]r");
+        match generate(&config) {
+            Err(Error::NotDiverse { found: 1, .. }) => {}
+            r => panic!("expected NotDiverse, got {:?}", r),
+        }
+
+        let path = std::env::temp_dir().join("string_studio_existing_test.txt");
+        std::fs::write(&path, "Calor\nKalur\n").unwrap();
+        config.phonetic = false;
+        config.number = 4;
+        // `--existing` passes the path from the current directory, which the
+        // config directory doesn't change.
+        config.existing_names = Some(path.clone());
+        config.config_dir = Some(PathBuf::from("/no/such/dir"));
+        let mut strings = generate(&config).unwrap();
+        strings.sort();
+        assert_eq!(strings, vec!["Calar", "Calur", "Kalar", "Kalor"]);
+        // A relative path from the config file is in its directory.
+        config.existing_names = Some(PathBuf::from("string_studio_existing_test.txt"));
+        config.config_dir = Some(std::env::temp_dir());
+        let mut strings = generate(&config).unwrap();
+        strings.sort();
+        assert_eq!(strings, vec!["Calar", "Calur", "Kalar", "Kalor"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn score_test() {
+        let mut config = Config {
+            pattern: CompositePattern::from("[a-z]{6}"),
+            number: 20,
+            seed: 3,
+            min_score: Some(0.7),
+            ..Default::default()
+        };
+        let scorer = Scorer::new(None).unwrap();
+        for record in generate_records(&config).unwrap() {
+            assert_eq!(record.score, Some(scorer.score(&record.text)));
+            assert!(record.score.unwrap() >= 0.7, "{:?}", record);
+        }
+
+        config.min_score = None;
+        config.rank = Some(5);
+        config.number = 50;
+        let all = generate_records(&config).unwrap();
+        let ranked = generate_ranked(&config).unwrap();
+        assert_eq!(ranked.len(), 5);
+        let best = all.iter().filter_map(|r| r.score).fold(0.0, f64::max);
+        assert_eq!(ranked[0].score, Some(best));
+        assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+
+        config.pattern = CompositePattern::from("[xzq]{5}");
+        config.min_score = Some(0.9);
+        assert!(matches!(generate(&config), Err(Error::LowScore { .. })));
     }
 }
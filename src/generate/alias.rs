@@ -0,0 +1,99 @@
+use rand::Rng;
+
+/// Walker/Vose alias table for sampling an index in constant time, with
+/// probability proportional to its weight.
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Build a table from `weights`. Returns `None` if no weight is positive.
+    pub fn new(weights: &[f64]) -> Option<Self> {
+        let total: f64 = weights.iter().sum();
+        if weights.is_empty() || total.is_nan() || total <= 0.0 {
+            return None;
+        }
+        let n = weights.len();
+        let mut prob: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|i| prob[*i] < 1.0);
+        while !small.is_empty() && !large.is_empty() {
+            let (s, l) = (small.pop().unwrap(), large.pop().unwrap());
+            alias[s] = l;
+            prob[l] -= 1.0 - prob[s];
+            if prob[l] < 1.0 {
+                small.push(l);
+            } else {
+                large.push(l);
+            }
+        }
+        // Whatever is left over is only off from 1 by rounding error.
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+        }
+        Some(Self { prob, alias })
+    }
+
+    /// A table with `n` equally likely indices.
+    pub fn uniform(n: usize) -> Option<Self> {
+        Self::new(&vec![1.0; n])
+    }
+
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    /// The probability of sampling index `i`.
+    pub fn probability(&self, i: usize) -> f64 {
+        let n = self.len() as f64;
+        let own = self.prob[i];
+        let aliased: f64 = (0..self.len())
+            .filter(|j| self.alias[*j] == i && *j != i)
+            .map(|j| 1.0 - self.prob[j])
+            .sum();
+        (own + aliased) / n
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.gen_range(0, self.prob.len());
+        if rng.gen::<f64>() < self.prob[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn alias_table_test() {
+        let weights = [8.0, 0.0, 1.0, 1.0];
+        let table = AliasTable::new(&weights).unwrap();
+        for (i, w) in weights.iter().enumerate() {
+            assert!((table.probability(i) - w / 10.0).abs() < 1e-9);
+        }
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut counts = [0; 4];
+        for _ in 0..10000 {
+            counts[table.sample(&mut rng)] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!(counts[0] > 7600 && counts[0] < 8400, "{:?}", counts);
+
+        assert!(AliasTable::new(&[]).is_none());
+        assert!(AliasTable::new(&[0.0, 0.0]).is_none());
+    }
+}
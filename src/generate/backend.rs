@@ -0,0 +1,225 @@
+//! Backends that strings can be generated with, and the registry that picks
+//! one by name.
+
+use std::fmt;
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use super::regex_gen::Record;
+use super::stats::LanguageSize;
+use crate::config::Config;
+use crate::error::Error;
+
+/// Name of the backend used when the config doesn't pick one.
+pub const DEFAULT_BACKEND: &str = "regex";
+/// Name of the backend that generates words from a trained Markov model.
+pub const MARKOV_BACKEND: &str = "markov";
+
+/// Name of the backend that expands the rules of a grammar.
+pub const GRAMMAR_BACKEND: &str = "grammar";
+
+/// Name of the backend that builds words syllable by syllable.
+pub const PHONOLOGY_BACKEND: &str = "phonology";
+
+/// Names of the built-in backends.
+const BUILTIN_BACKENDS: &[&str] = &[
+    DEFAULT_BACKEND,
+    MARKOV_BACKEND,
+    GRAMMAR_BACKEND,
+    PHONOLOGY_BACKEND,
+];
+
+/// How many strings to draw for each one output before giving up, whether
+/// a backend or the checks after it threw them away.
+pub const MAX_ATTEMPTS: u32 = 1000;
+
+/// Something that generates strings, or records with named fields, from a
+/// random number generator.
+///
+/// `generate` runs each string through the configured transforms, length
+/// limits and uniqueness checks, and hands every string its own seeded
+/// generator, so a backend only has to turn randomness into a record.
+pub trait Generator: Send + Sync {
+    /// Generate one record, or `Error::Miss` if the draw has to be thrown
+    /// away, for the caller to draw again.
+    fn generate_record(&self, rng: &mut dyn RngCore) -> Result<Record, Error>;
+
+    /// Generate one string, drawing again after each miss.
+    fn generate(&self, rng: &mut dyn RngCore) -> Result<String, Error> {
+        retry(|| self.generate_record(&mut *rng)).map(|r| r.text)
+    }
+
+    /// What strings are generated from, for error messages.
+    fn pattern(&self) -> &str;
+
+    /// How many distinct strings up to `max_len` characters there are, if
+    /// the backend can tell.
+    fn size(&self, _max_len: Option<usize>) -> Option<LanguageSize> {
+        None
+    }
+}
+
+/// Call `draw` until it doesn't miss, up to `MAX_ATTEMPTS` times, giving the
+/// error of the last miss if every draw misses.
+pub fn retry<T>(mut draw: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+    let mut last = None;
+    for _ in 0..MAX_ATTEMPTS {
+        match draw() {
+            Err(Error::Miss(error)) => last = Some(*error),
+            result => return result,
+        }
+    }
+    Err(last.unwrap())
+}
+
+/// Builds a generator from the configuration.
+pub type BackendFn = dyn Fn(&Config) -> Result<Box<dyn Generator>, Error> + Send + Sync;
+
+/// Backends registered by library users, on top of the built-in ones.
+#[derive(Clone, Default)]
+pub struct Backends {
+    custom: Vec<(String, Arc<BackendFn>)>,
+}
+
+impl Backends {
+    /// Register a backend under `name`, replacing any registered before with
+    /// that name, or a built-in one.
+    pub fn register<F>(&mut self, name: &str, build: F)
+    where
+        F: Fn(&Config) -> Result<Box<dyn Generator>, Error> + Send + Sync + 'static,
+    {
+        self.custom.retain(|(n, _)| n != name);
+        self.custom.push((String::from(name), Arc::new(build)));
+    }
+
+    /// The names of every backend, registered ones first.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.custom.iter().map(|(n, _)| n.as_str()).collect();
+        for name in BUILTIN_BACKENDS {
+            if !names.contains(name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Build the generator for the backend called `name`.
+    pub fn build(&self, name: &str, config: &Config) -> Result<Box<dyn Generator>, Error> {
+        if let Some((_, build)) = self.custom.iter().find(|(n, _)| n == name) {
+            return build(config);
+        }
+        match name {
+            DEFAULT_BACKEND => super::regex_generator(config),
+            MARKOV_BACKEND => super::markov::markov_generator(config),
+            GRAMMAR_BACKEND => super::grammar::grammar_generator(config),
+            PHONOLOGY_BACKEND => super::phonology::phonology_generator(config),
+            _ => {
+                let names: Vec<String> = self.names().iter().map(|n| format!("`{}`", n)).collect();
+                Err(Error::Config(format!(
+                    "No backend named `{}`; pick one of {}",
+                    name,
+                    names.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Backends {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CompositePattern, Fragment, Xform};
+    use crate::generate::{generate, stats};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct Fixed(&'static str);
+
+    impl Generator for Fixed {
+        fn generate_record(&self, _rng: &mut dyn RngCore) -> Result<Record, Error> {
+            Ok(Record::from(String::from(self.0)))
+        }
+
+        fn pattern(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn backends_test() {
+        let mut backends = Backends::default();
+        assert_eq!(backends.names(), vec!["regex", "markov", "grammar", "phonology"]);
+        backends.register("fixed", |_| Ok(Box::new(Fixed("one"))));
+        backends.register("fixed", |_| Ok(Box::new(Fixed("two"))));
+        assert_eq!(
+            backends.names(),
+            vec!["fixed", "regex", "markov", "grammar", "phonology"]
+        );
+
+        let mut config = Config::default();
+        let gen = backends.build("fixed", &config).unwrap();
+        let mut rng = rand::thread_rng();
+        assert_eq!(gen.generate(&mut rng).unwrap(), "two");
+        assert!(backends.build("regex", &config).is_ok());
+        assert!(backends.build("missing", &config).is_err());
+
+        config.backends = backends;
+        config.backend = Some(String::from("fixed"));
+        config.number = 3;
+        config.xforms = vec![Xform::UpperCase];
+        assert_eq!(generate(&config).unwrap(), vec!["TWO"; 3]);
+        assert!(stats(&config).is_err());
+    }
+
+    /// Misses every draw, counting them.
+    struct Missing(Arc<AtomicU32>);
+
+    impl Generator for Missing {
+        fn generate_record(&self, _rng: &mut dyn RngCore) -> Result<Record, Error> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(Error::Miss(Box::new(Error::Forbidden {
+                phonology: String::from("missing"),
+            })))
+        }
+
+        fn pattern(&self) -> &str {
+            "missing"
+        }
+    }
+
+    #[test]
+    fn miss_test() {
+        let draws = Arc::new(AtomicU32::new(0));
+        let counter = draws.clone();
+        let mut config = Config {
+            backend: Some(String::from("missing")),
+            number: 1,
+            ..Default::default()
+        };
+        config
+            .backends
+            .register("missing", move |_| Ok(Box::new(Missing(counter.clone()))));
+        // The misses are retried once, alongside the checks on each string,
+        // and the last one is reported.
+        assert!(matches!(generate(&config), Err(Error::Forbidden { .. })));
+        assert_eq!(draws.load(Ordering::Relaxed), MAX_ATTEMPTS);
+
+        // Inside a pattern, a miss throws away the whole string.
+        draws.store(0, Ordering::Relaxed);
+        config.backend = None;
+        config.pattern = CompositePattern::from(&["x", "@word@"] as &[&str]);
+        config.fragments = vec![Fragment {
+            name: String::from("word"),
+            backend: Some(String::from("missing")),
+            ..Default::default()
+        }];
+        assert!(matches!(generate(&config), Err(Error::Forbidden { .. })));
+        assert_eq!(draws.load(Ordering::Relaxed), MAX_ATTEMPTS);
+    }
+}
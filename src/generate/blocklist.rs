@@ -0,0 +1,173 @@
+//! Rejecting strings that spell blocked words, even disguised.
+
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Profanity, slurs and other words no generated name should contain.
+const BUILTIN_WORDS: &[&str] = &[
+    "bastard", "bitch", "chink", "cunt", "dildo", "fagg", "fuck", "hitler", "jizz", "kike", "nazi",
+    "nigg", "penis", "porn", "retard", "shit", "slut", "tranny", "twat", "vagina", "wank", "whore",
+];
+
+/// Letters with diacritics and the letters they're folded into.
+const DIACRITICS: &[(&str, char)] = &[
+    ("àáâãäåāăą", 'a'),
+    ("çćĉċč", 'c'),
+    ("ďđ", 'd'),
+    ("èéêëēĕėęě", 'e'),
+    ("ĝğġģ", 'g'),
+    ("ĥħ", 'h'),
+    ("ìíîïĩīĭįı", 'i'),
+    ("ĵ", 'j'),
+    ("ķ", 'k'),
+    ("ĺļľŀł", 'l'),
+    ("ñńņňŉ", 'n'),
+    ("òóôõöøōŏő", 'o'),
+    ("ŕŗř", 'r'),
+    ("śŝşšß", 's'),
+    ("ţťŧ", 't'),
+    ("ùúûüũūŭůűų", 'u'),
+    ("ŵ", 'w'),
+    ("ýÿŷ", 'y'),
+    ("źżž", 'z'),
+];
+
+/// Digits and symbols that stand in for letters, and those letters.
+const LEETSPEAK: &[(char, char)] = &[
+    ('0', 'o'),
+    ('(', 'c'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('3', 'e'),
+    ('4', 'a'),
+    ('@', 'a'),
+    ('5', 's'),
+    ('$', 's'),
+    ('7', 't'),
+    ('+', 't'),
+    ('8', 'b'),
+    ('9', 'g'),
+    ('|', 'l'),
+];
+
+/// `c` lowercased, without diacritics, and as the letter it stands in for.
+fn fold_char(c: char) -> Option<char> {
+    let c = c.to_lowercase().next().unwrap_or(c);
+    if ('\u{300}'..='\u{36f}').contains(&c) {
+        // A combining diacritic.
+        return None;
+    }
+    if let Some((_, base)) = DIACRITICS.iter().find(|(marked, _)| marked.contains(c)) {
+        return Some(*base);
+    }
+    if let Some((_, letter)) = LEETSPEAK.iter().find(|(leet, _)| *leet == c) {
+        return Some(*letter);
+    }
+    Some(c).filter(|c| c.is_alphanumeric())
+}
+
+/// `s` folded the way blocked words are matched against: lowercased,
+/// without diacritics, with leetspeak read as letters, with each run of
+/// whitespace as one space, and with everything else that isn't a letter or
+/// digit dropped.
+fn fold(s: &str) -> String {
+    s.split_whitespace()
+        .map(|word| word.chars().filter_map(fold_char).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Words that generated strings may not contain.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    /// Each word, folded, and as it was given.
+    words: Vec<(String, String)>,
+}
+
+impl Blocklist {
+    /// A blocklist of `words`, ignoring any that are empty once folded.
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut list = Self::default();
+        list.extend(words);
+        list
+    }
+
+    /// The built-in blocklist.
+    pub fn builtin() -> Self {
+        Self::new(BUILTIN_WORDS.iter().copied())
+    }
+
+    /// Add `words` to the list.
+    pub fn extend<'a>(&mut self, words: impl IntoIterator<Item = &'a str>) {
+        for word in words {
+            let folded = fold(word);
+            if !folded.is_empty() {
+                self.words.push((folded, String::from(word.trim())));
+            }
+        }
+    }
+
+    /// Add the words in the file at `path`, one per line. Blank lines and
+    /// lines starting with `#` are skipped.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<(), Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(source) => {
+                return Err(Error::DataIo {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        self.extend(text.lines().filter(|l| !l.trim_start().starts_with('#')));
+        Ok(())
+    }
+
+    /// The first blocked word `s` contains, if any.
+    ///
+    /// Both are folded first, so `Fück`, `F.U.C.K` and `fu(k` all contain
+    /// `fuck`. A `1` is read as both an `i` and an `l`. Words are matched
+    /// within the words of `s`, so `Tess Hitman` doesn't contain `shit`.
+    pub fn find(&self, s: &str) -> Option<&str> {
+        let variants = [fold(s), fold(&s.replace('1', "l"))];
+        self.words
+            .iter()
+            .find(|(word, _)| variants.iter().any(|v| v.contains(word.as_str())))
+            .map(|(_, word)| word.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocklist_test() {
+        let mut list = Blocklist::builtin();
+        list.extend(["Grümpel", "b0rk", "  ", "-?-"]);
+        for s in [
+            "Shitaro",
+            "Sh1tt",
+            "Quicksh!t",
+            "5hit",
+            "S.H.I.T",
+            "shït",
+            "Gru\u{308}mpelkin",
+            "Borkan",
+            "Old Wank",
+        ] {
+            assert!(list.find(s).is_some(), "{}", s);
+        }
+        assert_eq!(list.find("Xgrumpelx"), Some("Grümpel"));
+        assert_eq!(list.find("BoRk"), Some("b0rk"));
+        for s in ["Melora", "Grape", "Shiloh", "Kessit", "Tess Hitman", "Wa nk"] {
+            assert_eq!(list.find(s), None, "{}", s);
+        }
+        // Phrases match across words, however they're spaced.
+        assert_eq!(Blocklist::new(["red  herring"]).find("Red Herringly"), Some("red  herring"));
+        // A `1` can be an `l` too.
+        assert_eq!(Blocklist::new(["slut"]).find("S1ut"), Some("slut"));
+    }
+}
@@ -0,0 +1,379 @@
+//! A deterministic automaton for the strings a compiled pattern can generate.
+//!
+//! Anchors and word boundaries are treated as always holding, so the
+//! automaton accepts a superset of the pattern's language when it has any.
+//! Alternation branches with no weight, and optionals with a probability of
+//! 0 or 1, are resolved the way the generator resolves them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+use super::node::Node;
+use crate::error::Error;
+
+/// Upper bound on automaton size, to fail cleanly on huge patterns.
+pub const MAX_STATES: usize = 20_000;
+
+const SURROGATE_START: u32 = 0xD800;
+const SURROGATE_END: u32 = 0xDFFF;
+
+/// Number of Unicode scalar values in `lo..=hi`, skipping the surrogate gap.
+pub fn interval_len(lo: u32, hi: u32) -> u32 {
+    let overlap_lo = lo.max(SURROGATE_START);
+    let overlap_hi = hi.min(SURROGATE_END);
+    let surrogates = if overlap_lo <= overlap_hi {
+        overlap_hi - overlap_lo + 1
+    } else {
+        0
+    };
+    hi - lo + 1 - surrogates
+}
+
+/// The `n`th Unicode scalar value in `lo..=hi`, skipping the surrogate gap.
+pub fn nth_in_interval(lo: u32, n: u32) -> char {
+    let mut c = lo + n;
+    if lo < SURROGATE_START && c >= SURROGATE_START {
+        c += SURROGATE_END - SURROGATE_START + 1;
+    }
+    std::char::from_u32(c).unwrap()
+}
+
+enum NfaState {
+    Split(Vec<usize>),
+    Range(u32, u32, usize),
+    Match,
+}
+
+struct Nfa {
+    states: Vec<NfaState>,
+}
+
+impl Nfa {
+    fn push(&mut self, state: NfaState) -> Result<usize, ()> {
+        if self.states.len() >= MAX_STATES * 16 {
+            return Err(());
+        }
+        self.states.push(state);
+        Ok(self.states.len() - 1)
+    }
+
+    /// Add states for `node` that continue to `next`, returning the entry state.
+    fn build(&mut self, node: &Node, next: usize) -> Result<usize, ()> {
+        match node {
+            Node::Empty | Node::Assertion(_) => Ok(next),
+            Node::Literal(c) => self.push(NfaState::Range(*c as u32, *c as u32, next)),
+            Node::Class(cls) => {
+                let mut starts = Vec::with_capacity(cls.ranges().len());
+                for r in cls.iter() {
+                    starts.push(self.push(NfaState::Range(
+                        r.start() as u32,
+                        r.end() as u32,
+                        next,
+                    ))?);
+                }
+                self.push(NfaState::Split(starts))
+            }
+            Node::Concat(nodes) => {
+                let mut cur = next;
+                for n in nodes.iter().rev() {
+                    cur = self.build(n, cur)?;
+                }
+                Ok(cur)
+            }
+            Node::Alternation {
+                branches, weights, ..
+            } => {
+                let mut starts = Vec::with_capacity(branches.len());
+                for n in Node::live_branches(branches, weights) {
+                    starts.push(self.build(n, next)?);
+                }
+                self.push(NfaState::Split(starts))
+            }
+            Node::Repetition {
+                node,
+                min,
+                max,
+                probability,
+            } => {
+                let (min, max) = Node::repeat_range(*min, *max, *probability);
+                let mut cur = match max {
+                    Some(max) => {
+                        let mut cur = next;
+                        for _ in min..max {
+                            let body = self.build(node, cur)?;
+                            cur = self.push(NfaState::Split(vec![body, cur]))?;
+                        }
+                        cur
+                    }
+                    None => {
+                        let split = self.push(NfaState::Split(Vec::new()))?;
+                        let body = self.build(node, split)?;
+                        self.states[split] = NfaState::Split(vec![body, next]);
+                        split
+                    }
+                };
+                for _ in 0..min {
+                    cur = self.build(node, cur)?;
+                }
+                Ok(cur)
+            }
+            Node::Group(node) | Node::Capture { node, .. } => self.build(node, next),
+            // Any text the group can generate, or none if it wasn't used,
+            // which is a superset of what the back-reference repeats.
+            Node::BackRef { node, .. } => {
+                let body = self.build(node, next)?;
+                self.push(NfaState::Split(vec![body, next]))
+            }
+            // Patterns with rules or sources never get an automaton.
+            Node::Rule(_) | Node::Source(_) => Err(()),
+        }
+    }
+
+    fn closure(&self, starts: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut set = BTreeSet::new();
+        let mut stack: Vec<usize> = starts.into_iter().collect();
+        while let Some(s) = stack.pop() {
+            if set.insert(s) {
+                if let NfaState::Split(next) = &self.states[s] {
+                    stack.extend(next.iter().copied());
+                }
+            }
+        }
+        set
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DfaState {
+    pub accept: bool,
+    /// Disjoint, sorted `(lo, hi, target)` transitions on `lo..=hi`.
+    pub trans: Vec<(u32, u32, usize)>,
+}
+
+/// How much work analyzing a pattern may take before giving up.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Most automaton states to build.
+    pub states: usize,
+    /// When to give up, and how long that allowed, if ever.
+    pub deadline: Option<(Instant, Duration)>,
+}
+
+impl Limits {
+    /// At most `states` automaton states, and `time` from now.
+    pub fn timed(states: usize, time: Duration) -> Self {
+        Self {
+            states,
+            deadline: Some((Instant::now() + time, time)),
+        }
+    }
+
+    fn exceeded(pattern: &str, limit: String) -> Error {
+        Error::AnalysisLimit {
+            pattern: String::from(pattern),
+            limit,
+        }
+    }
+
+    fn check_time(&self, pattern: &str) -> Result<(), Error> {
+        match self.deadline {
+            Some((deadline, time)) if Instant::now() > deadline => Err(Self::exceeded(
+                pattern,
+                format!("{} seconds", time.as_secs_f64()),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            states: MAX_STATES,
+            deadline: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    pub states: Vec<DfaState>,
+    pub start: usize,
+}
+
+impl Dfa {
+    pub fn new(node: &Node, pattern: &str) -> Result<Self, Error> {
+        Self::with_limits(node, pattern, &Limits::default())
+    }
+
+    /// The automaton for `node`, or an error if building it goes past
+    /// `limits`.
+    pub fn with_limits(node: &Node, pattern: &str, limits: &Limits) -> Result<Self, Error> {
+        let max_states = limits.states;
+        let too_large =
+            || Limits::exceeded(pattern, format!("{} automaton states", max_states));
+        let mut nfa = Nfa {
+            states: vec![NfaState::Match],
+        };
+        let nfa_start = nfa.build(node, 0).map_err(|_| too_large())?;
+
+        let mut ids: HashMap<BTreeSet<usize>, usize> = HashMap::new();
+        let mut sets: Vec<BTreeSet<usize>> = Vec::new();
+        let mut states: Vec<DfaState> = Vec::new();
+
+        let start_set = nfa.closure(Some(nfa_start));
+        ids.insert(start_set.clone(), 0);
+        sets.push(start_set);
+
+        let mut i = 0;
+        while i < sets.len() {
+            limits.check_time(pattern)?;
+            let ranges: Vec<(u32, u32, usize)> = sets[i]
+                .iter()
+                .filter_map(|s| match nfa.states[*s] {
+                    NfaState::Range(lo, hi, next) => Some((lo, hi, next)),
+                    _ => None,
+                })
+                .collect();
+            let accept = sets[i].iter().any(|s| matches!(nfa.states[*s], NfaState::Match));
+
+            // Sweep the range bounds, splitting them into intervals where the
+            // same targets apply. Each set of targets needs its closure only
+            // once, however many intervals lead there.
+            let mut events: Vec<(u32, usize, bool)> = ranges
+                .iter()
+                .flat_map(|r| vec![(r.0, r.2, true), (r.1 + 1, r.2, false)])
+                .collect();
+            events.sort_unstable();
+            let mut active: BTreeMap<usize, usize> = BTreeMap::new();
+            let mut targets_of: HashMap<Vec<usize>, usize> = HashMap::new();
+            let mut trans: Vec<(u32, u32, usize)> = Vec::new();
+            let mut e = 0;
+            while e < events.len() {
+                let lo = events[e].0;
+                while e < events.len() && events[e].0 == lo {
+                    let (_, next, starts) = events[e];
+                    let n = active.entry(next).or_insert(0);
+                    if starts {
+                        *n += 1;
+                    } else {
+                        *n -= 1;
+                        if *n == 0 {
+                            active.remove(&next);
+                        }
+                    }
+                    e += 1;
+                }
+                let hi = match events.get(e) {
+                    Some(ev) => ev.0 - 1,
+                    None => break,
+                };
+                if active.is_empty() || interval_len(lo, hi) == 0 {
+                    continue;
+                }
+                let nexts: Vec<usize> = active.keys().copied().collect();
+                let target = match targets_of.get(&nexts) {
+                    Some(id) => *id,
+                    None => {
+                        let targets = nfa.closure(nexts.iter().copied());
+                        let id = match ids.get(&targets) {
+                            Some(id) => *id,
+                            None => {
+                                if sets.len() >= max_states {
+                                    return Err(too_large());
+                                }
+                                ids.insert(targets.clone(), sets.len());
+                                sets.push(targets);
+                                sets.len() - 1
+                            }
+                        };
+                        targets_of.insert(nexts, id);
+                        id
+                    }
+                };
+                match trans.last_mut() {
+                    Some(last) if last.2 == target && last.1 + 1 == lo => last.1 = hi,
+                    _ => trans.push((lo, hi, target)),
+                }
+            }
+            states.push(DfaState { accept, trans });
+            i += 1;
+        }
+
+        Ok(Self { states, start: 0 })
+    }
+
+    /// `live[k][s]` is whether state `s` can reach an accepting state in
+    /// exactly `k` characters, for `k` in `0..=max_len`.
+    pub fn live_table(&self, max_len: usize) -> Vec<Vec<bool>> {
+        let mut live = vec![self.states.iter().map(|s| s.accept).collect::<Vec<bool>>()];
+        for k in 1..=max_len {
+            let prev = &live[k - 1];
+            let row = self
+                .states
+                .iter()
+                .map(|s| s.trans.iter().any(|t| prev[t.2]))
+                .collect();
+            live.push(row);
+        }
+        live
+    }
+
+    /// Number of accepted strings of at most `max_len` characters, or an
+    /// error if counting them goes past the time in `limits`.
+    pub fn count(&self, max_len: usize, limits: &Limits, pattern: &str) -> Result<BigUint, Error> {
+        // `ways[s]` is the number of strings of the current length that take
+        // state `s` to an accepting state.
+        let mut ways: Vec<BigUint> = self
+            .states
+            .iter()
+            .map(|s| BigUint::from(s.accept as u32))
+            .collect();
+        // The number of characters leading from each state to each target.
+        let widths: Vec<Vec<(usize, u64)>> = self
+            .states
+            .iter()
+            .map(|s| {
+                let mut widths: BTreeMap<usize, u64> = BTreeMap::new();
+                for t in &s.trans {
+                    *widths.entry(t.2).or_insert(0) += u64::from(interval_len(t.0, t.1));
+                }
+                widths.into_iter().collect()
+            })
+            .collect();
+        let mut total = ways[self.start].clone();
+        for _ in 0..max_len {
+            limits.check_time(pattern)?;
+            ways = widths
+                .iter()
+                .map(|w| {
+                    w.iter()
+                        .filter(|(target, _)| !ways[*target].is_zero())
+                        .map(|(target, width)| &ways[*target] * *width)
+                        .sum()
+                })
+                .collect();
+            if ways.iter().all(Zero::is_zero) {
+                break;
+            }
+            total += &ways[self.start];
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_test() {
+        assert_eq!(interval_len(0x61, 0x7A), 26);
+        assert_eq!(interval_len(0xD7FF, 0xE000), 2);
+        assert_eq!(interval_len(0xD800, 0xDFFF), 0);
+        assert_eq!(nth_in_interval(0xD7FF, 1), '\u{E000}');
+    }
+}
@@ -0,0 +1,229 @@
+//! Rejecting strings that look or sound like ones already accepted.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Whether `c` is a lowercase vowel, counting `y`.
+pub(crate) fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+/// A rough key for how `s` sounds, in the spirit of Metaphone: letters that
+/// sound alike share a symbol, vowels after the first letter are dropped and
+/// repeated sounds count once, so `Kalor`, `Kalar` and `Calor` share `klr`.
+pub fn phonetic_key(s: &str) -> String {
+    let letters: Vec<char> = s
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    let mut key = String::new();
+    let mut buf = [0; 4];
+    for (i, &c) in letters.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| letters[i]);
+        let next = letters.get(i + 1).copied();
+        let soft = matches!(next, Some('e' | 'i' | 'y'));
+        let sounds: &str = match c {
+            _ if is_vowel(c) => {
+                if i == 0 {
+                    "a"
+                } else {
+                    ""
+                }
+            }
+            'c' if next == Some('h') => "x",
+            'c' if soft => "s",
+            'c' | 'k' | 'q' => "k",
+            'd' => "t",
+            'g' if soft => "j",
+            'g' => "k",
+            'h' if matches!(prev, Some('c' | 'p' | 's' | 't')) => "",
+            'h' | 'w' if !next.is_some_and(is_vowel) => "",
+            'p' if next == Some('h') => "f",
+            's' if next == Some('h') => "x",
+            't' if next == Some('h') => "0",
+            'v' => "f",
+            'x' => "ks",
+            'z' => "s",
+            _ => c.encode_utf8(&mut buf),
+        };
+        for sound in sounds.chars() {
+            if !key.ends_with(sound) {
+                key.push(sound);
+            }
+        }
+    }
+    key
+}
+
+/// The number of single character insertions, deletions and substitutions
+/// it takes to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Why a string was rejected, naming the string it's too much like.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Within fewer than the minimum number of edits.
+    Spelling { other: String, distance: usize },
+    /// Has the same phonetic key.
+    Sound { other: String, key: String },
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spelling { other, distance } => {
+                let edits = if *distance == 1 { "edit" } else { "edits" };
+                write!(f, "it's {} {} from `{}`", distance, edits, other)
+            }
+            Self::Sound { other, key } => {
+                write!(f, "it sounds like `{}` (both `{}`)", other, key)
+            }
+        }
+    }
+}
+
+/// Strings accepted so far, for rejecting new ones too much like them.
+/// Comparisons ignore case.
+#[derive(Debug, Clone, Default)]
+pub struct Diversity {
+    min_distance: usize,
+    phonetic: bool,
+    /// Each accepted string, lowercased, and as it was accepted.
+    accepted: Vec<(String, String)>,
+    /// The accepted string each phonetic key came from.
+    keys: HashMap<String, String>,
+}
+
+impl Diversity {
+    /// Reject strings fewer than `min_distance` edits from an accepted one,
+    /// and if `phonetic` is set, strings that sound like one.
+    pub fn new(min_distance: usize, phonetic: bool) -> Self {
+        Self {
+            min_distance,
+            phonetic,
+            ..Default::default()
+        }
+    }
+
+    /// Accept `s`, whether or not it conflicts with another string.
+    pub fn accept(&mut self, s: &str) {
+        if self.phonetic {
+            let key = phonetic_key(s);
+            if !key.is_empty() {
+                self.keys.entry(key).or_insert_with(|| String::from(s));
+            }
+        }
+        self.accepted.push((s.to_lowercase(), String::from(s)));
+    }
+
+    /// Accept the names in the file at `path`, one per line, skipping blank
+    /// lines.
+    pub fn accept_file(&mut self, path: &Path) -> Result<(), Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(source) => {
+                return Err(Error::DataIo {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        for name in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            self.accept(name);
+        }
+        Ok(())
+    }
+
+    /// The first way `s` is too much like an accepted string, if any.
+    pub fn conflict(&self, s: &str) -> Option<Conflict> {
+        if self.phonetic {
+            let key = phonetic_key(s);
+            if let Some(other) = self.keys.get(&key) {
+                return Some(Conflict::Sound {
+                    other: other.clone(),
+                    key,
+                });
+            }
+        }
+        let lower = s.to_lowercase();
+        let len = lower.chars().count();
+        self.accepted
+            .iter()
+            // Strings whose lengths differ by the minimum are far enough
+            // apart already.
+            .filter(|(other, _)| len.abs_diff(other.chars().count()) < self.min_distance)
+            .map(|(other, s)| (edit_distance(&lower, other), s))
+            .find(|(distance, _)| *distance < self.min_distance)
+            .map(|(distance, other)| Conflict::Spelling {
+                other: other.clone(),
+                distance,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phonetic_key_test() {
+        assert_eq!(phonetic_key("Kalor"), "klr");
+        assert_eq!(phonetic_key("Kalar"), "klr");
+        assert_eq!(phonetic_key("Calor"), "klr");
+        assert_eq!(phonetic_key("Philippa"), phonetic_key("Filipa"));
+        assert_eq!(phonetic_key("Cyra"), phonetic_key("Sira"));
+        assert_eq!(phonetic_key("Aethel"), "a0l");
+        assert_ne!(phonetic_key("Kalor"), phonetic_key("Malor"));
+        assert_eq!(phonetic_key("42"), "");
+    }
+
+    #[test]
+    fn diversity_test() {
+        assert_eq!(edit_distance("kalor", "kalar"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let mut diversity = Diversity::new(2, false);
+        diversity.accept("Kalor");
+        assert_eq!(
+            diversity.conflict("kalar"),
+            Some(Conflict::Spelling {
+                other: String::from("Kalor"),
+                distance: 1
+            })
+        );
+        assert_eq!(
+            diversity
+                .conflict("Calor")
+                .map(|c| c.to_string())
+                .as_deref(),
+            Some("it's 1 edit from `Kalor`")
+        );
+        assert_eq!(diversity.conflict("Kolar"), None);
+
+        let mut diversity = Diversity::new(1, true);
+        diversity.accept("Kalor");
+        assert!(matches!(
+            diversity.conflict("Calar"),
+            Some(Conflict::Sound { .. })
+        ));
+        assert_eq!(diversity.conflict("Melora"), None);
+    }
+}
@@ -0,0 +1,157 @@
+use regex::Regex;
+
+use super::dfa::{interval_len, nth_in_interval, Dfa};
+
+/// A step of the depth-first walk: the state reached, and the next
+/// transition and character within it to try.
+struct Frame {
+    state: usize,
+    trans: usize,
+    offset: u32,
+}
+
+/// Iterator over every string a pattern can generate, in shortlex order:
+/// shorter strings first, then by codepoint.
+pub struct Enumerate {
+    dfa: Dfa,
+    live: Vec<Vec<bool>>,
+    /// Length of the strings currently being enumerated.
+    len: usize,
+    stack: Vec<Frame>,
+    buf: String,
+    /// Filters out strings that break the pattern's anchors or word
+    /// boundaries, which the automaton ignores.
+    verifier: Option<Regex>,
+}
+
+impl Enumerate {
+    pub(crate) fn new(dfa: Dfa, min_len: usize, max_len: usize, verifier: Option<Regex>) -> Self {
+        let live = dfa.live_table(max_len);
+        Self {
+            dfa,
+            live,
+            len: min_len,
+            stack: Vec::new(),
+            buf: String::new(),
+            verifier,
+        }
+    }
+
+    fn accepts(&self, s: &str) -> bool {
+        self.verifier.as_ref().map_or(true, |re| re.is_match(s))
+    }
+}
+
+impl Iterator for Enumerate {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            if self.stack.is_empty() {
+                // Start on the next length.
+                if self.len >= self.live.len() {
+                    return None;
+                }
+                let len = self.len;
+                self.len += 1;
+                if !self.live[len][self.dfa.start] {
+                    continue;
+                }
+                if len == 0 {
+                    if self.accepts("") {
+                        return Some(String::new());
+                    }
+                    continue;
+                }
+                self.len = len;
+                self.buf.clear();
+                self.stack.push(Frame {
+                    state: self.dfa.start,
+                    trans: 0,
+                    offset: 0,
+                });
+            }
+
+            let remaining = self.len - (self.stack.len() - 1) - 1;
+            let frame = self.stack.last_mut().unwrap();
+            let trans = &self.dfa.states[frame.state].trans;
+            let mut next = None;
+            while let Some(&(lo, hi, target)) = trans.get(frame.trans) {
+                if self.live[remaining][target] && frame.offset < interval_len(lo, hi) {
+                    next = Some((nth_in_interval(lo, frame.offset), target));
+                    frame.offset += 1;
+                    break;
+                }
+                frame.trans += 1;
+                frame.offset = 0;
+            }
+
+            match next {
+                Some((c, target)) => {
+                    if remaining == 0 {
+                        self.buf.push(c);
+                        let s = self.buf.clone();
+                        self.buf.pop();
+                        if self.accepts(&s) {
+                            return Some(s);
+                        }
+                    } else {
+                        self.buf.push(c);
+                        self.stack.push(Frame {
+                            state: target,
+                            trans: 0,
+                            offset: 0,
+                        });
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    if self.stack.is_empty() {
+                        self.len += 1;
+                    } else {
+                        self.buf.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::regex_gen::RegexGen;
+
+    fn enumerate(pattern: &str, max_len: Option<usize>) -> Vec<String> {
+        RegexGen::new(pattern)
+            .unwrap()
+            .enumerate(max_len)
+            .unwrap()
+            .collect()
+    }
+
+    #[test]
+    fn enumerate_test() {
+        assert_eq!(
+            enumerate("(b|a)(c|ba)?", None),
+            vec!["a", "b", "ac", "bc", "aba", "bba"]
+        );
+        assert_eq!(enumerate("[a-c]{2}", None).len(), 9);
+        assert_eq!(enumerate("x*", Some(3)), vec!["", "x", "xx", "xxx"]);
+        assert_eq!(enumerate("a|a|{=0}b", None), vec!["a"]);
+        assert_eq!(enumerate("ab{?0}c{?1}", None), vec!["ac"]);
+        assert_eq!(enumerate("[\u{d7ff}-\u{e000}]", None), vec!["\u{d7ff}", "\u{e000}"]);
+    }
+
+    #[test]
+    fn enumerate_assertion_test() {
+        assert_eq!(enumerate(r"(a|\ba| )\b", None), vec!["a"]);
+        assert_eq!(enumerate("(x|y)?^z", None), vec!["z"]);
+    }
+
+    #[test]
+    fn enumerate_unbounded_test() {
+        let gen = RegexGen::new("a+b").unwrap();
+        assert!(gen.enumerate(None).is_err());
+        assert_eq!(gen.enumerate(Some(2)).unwrap().collect::<Vec<_>>(), vec!["ab"]);
+    }
+}
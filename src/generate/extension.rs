@@ -0,0 +1,517 @@
+//! String Studio's extensions to the regular expression syntax.
+//!
+//! * `{=W}` at the start of an alternation branch gives that branch the
+//!   weight `W` (default 1), e.g. `({=4}[bcdfg]|{=1}[aeiou])`.
+//! * `{?P}` after an expression makes it optional with probability `P`,
+//!   e.g. `ka'{?0.1}ri`.
+//! * `\N` and `\k<name>` repeat the text an earlier capture group generated,
+//!   by number or name, e.g. `([a-z]{3})-\1`.
+//! * `{&name}` generates an entry of the word list `name`, which is what
+//!   word list fragments assemble to, e.g. `{&animal}{2}`.
+//! * `#name#` expands the grammar rule `name`, in grammar patterns only,
+//!   e.g. `the (#adj#|#noun#)`. Elsewhere `#` is a literal.
+//!
+//! The extensions are rewritten into empty capture groups with reserved names
+//! (markers) that `regex_syntax` keeps in the HIR. Each marker indexes into a
+//! table of annotations, which the generator picks up when compiling the HIR.
+
+use std::ops::Range;
+
+use crate::error::Error;
+
+const MARKER_PREFIX: &str = "__ss";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    /// Weight of the alternation branch the marker starts.
+    Weight(f64),
+    /// Probability of the `?` repetition just before the marker.
+    Probability(f64),
+    /// A back-reference to the capture group with this index in the
+    /// rewritten pattern.
+    BackRef(u32),
+    /// An entry of the word list with this name.
+    WordList(String),
+    /// An expansion of the grammar rule with this index.
+    Rule(usize),
+}
+
+/// A capture group opening, or a numbered back-reference, in a pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupToken {
+    Open,
+    /// A back-reference to group `number`, at `span`.
+    Reference { number: usize, span: Range<usize> },
+}
+
+/// The capture groups `pattern` opens and the numbered back-references it
+/// makes, in order.
+pub fn scan_groups(pattern: &str) -> Vec<GroupToken> {
+    let bytes = pattern.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                let digits = digits_len(&pattern[i + 1..]);
+                if digits > 0 {
+                    let span = i..i + 1 + digits;
+                    if let Ok(number) = pattern[i + 1..span.end].parse() {
+                        tokens.push(GroupToken::Reference { number, span });
+                    }
+                    i += 1 + digits;
+                } else {
+                    i += 1 + pattern[i + 1..].chars().next().map_or(0, char::len_utf8);
+                }
+            }
+            b'[' => i = class_end(pattern, i),
+            b'(' => {
+                if bytes.get(i + 1) != Some(&b'?') || pattern[i..].starts_with("(?P<") {
+                    tokens.push(GroupToken::Open);
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    tokens
+}
+
+/// Length of the run of ASCII digits at the start of `s`.
+fn digits_len(s: &str) -> usize {
+    s.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+/// A capture group in the original pattern.
+struct Group {
+    name: Option<String>,
+    /// Capture index in the rewritten pattern, which counts markers too.
+    index: u32,
+    closed: bool,
+}
+
+/// How a back-reference names its group.
+enum Reference {
+    Number(usize),
+    Name(String),
+}
+
+impl Reference {
+    fn find<'a>(&self, groups: &'a [Group]) -> Option<&'a Group> {
+        match self {
+            Self::Number(n) => n.checked_sub(1).and_then(|i| groups.get(i)),
+            Self::Name(name) => groups.iter().find(|g| g.name.as_ref() == Some(name)),
+        }
+    }
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "group {}", n),
+            Self::Name(name) => write!(f, "group `{}`", name),
+        }
+    }
+}
+
+/// A pattern with its extensions rewritten into markers.
+#[derive(Debug)]
+pub struct Extended {
+    pub pattern: String,
+    pub annotations: Vec<Annotation>,
+    /// Offset in the original pattern of every byte of `pattern`, plus one
+    /// for the end of the pattern.
+    offsets: Vec<usize>,
+}
+
+impl Extended {
+    /// Map a byte range of the rewritten pattern back to the original pattern.
+    pub fn original_span(&self, span: Range<usize>) -> Range<usize> {
+        let start = self.offsets[span.start.min(self.offsets.len() - 1)];
+        let end = self.offsets[span.end.min(self.offsets.len() - 1)];
+        start..end.max(start)
+    }
+}
+
+/// The annotation index of a marker group name, if it is one.
+pub fn marker_index(name: &str) -> Option<usize> {
+    name.strip_prefix(MARKER_PREFIX)?.parse().ok()
+}
+
+struct Rewriter<'a> {
+    original: &'a str,
+    out: Extended,
+    /// Capture groups written so far, markers included.
+    captures: u32,
+}
+
+impl<'a> Rewriter<'a> {
+    fn copy(&mut self, range: Range<usize>) {
+        self.out.pattern += &self.original[range.clone()];
+        self.out.offsets.extend(range);
+    }
+    fn marker(&mut self, prefix: &str, annotation: Annotation, at: usize) {
+        let index = self.out.annotations.len();
+        self.out.annotations.push(annotation);
+        self.captures += 1;
+        let s = format!("{}(?P<{}{}>)", prefix, MARKER_PREFIX, index);
+        self.out.offsets.extend(std::iter::repeat(at).take(s.len()));
+        self.out.pattern += &s;
+    }
+    fn error(&self, span: Range<usize>, message: &str) -> Error {
+        Error::Extension {
+            pattern: String::from(self.original),
+            span,
+            message: String::from(message),
+        }
+    }
+}
+
+/// Byte offset just past the class starting at `start`, which is a `[`.
+fn class_end(pattern: &str, start: usize) -> usize {
+    let bytes = pattern.as_bytes();
+    let mut i = start + 1;
+    if bytes.get(i) == Some(&b'^') {
+        i += 1;
+    }
+    // A `]` right at the start of a class is a literal.
+    if bytes.get(i) == Some(&b']') {
+        i += 1;
+    }
+    let mut depth = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+/// Rewrite the extensions in `pattern` into markers, with `#name#` referring
+/// to one of `rules` if there are any.
+pub fn preprocess(pattern: &str, rules: &[String]) -> Result<Extended, Error> {
+    let mut rw = Rewriter {
+        original: pattern,
+        out: Extended {
+            pattern: String::with_capacity(pattern.len()),
+            annotations: Vec::new(),
+            offsets: Vec::with_capacity(pattern.len() + 1),
+        },
+        captures: 0,
+    };
+    let mut groups: Vec<Group> = Vec::new();
+    // For each open parenthesis, the group it opened if it's a capture group.
+    let mut parens: Vec<Option<usize>> = Vec::new();
+    // Back-references to groups that weren't open yet, kept to report once
+    // every group is known.
+    let mut unresolved: Vec<(Range<usize>, Reference)> = Vec::new();
+    let bytes = pattern.as_bytes();
+    let mut branch_start = true;
+    let mut after_probability = false;
+    let mut quantifier = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let was_probability = after_probability;
+        after_probability = false;
+        let was_quantifier = quantifier;
+        quantifier = false;
+        match bytes[i] {
+            b'\\' if digits_len(&pattern[i + 1..]) > 0 || pattern[i + 1..].starts_with("k<") => {
+                let (end, reference) = if bytes[i + 1] == b'k' {
+                    let end = match pattern[i..].find('>') {
+                        Some(n) => i + n + 1,
+                        None => return Err(rw.error(i..bytes.len(), "unclosed back-reference")),
+                    };
+                    (end, Reference::Name(String::from(&pattern[i + 3..end - 1])))
+                } else {
+                    let end = i + 1 + digits_len(&pattern[i + 1..]);
+                    match pattern[i + 1..end].parse() {
+                        Ok(n) if n > 0 => (end, Reference::Number(n)),
+                        _ => return Err(rw.error(i..end, "groups are numbered from 1")),
+                    }
+                };
+                match reference.find(&groups) {
+                    Some(g) if !g.closed => {
+                        return Err(rw.error(
+                            i..end,
+                            "a back-reference can't be inside the group it refers to",
+                        ))
+                    }
+                    Some(g) => rw.marker("", Annotation::BackRef(g.index), i),
+                    None => unresolved.push((i..end, reference)),
+                }
+                i = end;
+                branch_start = false;
+            }
+            b'\\' => {
+                let len = pattern[i + 1..].chars().next().map_or(0, char::len_utf8);
+                let mut end = i + 1 + len;
+                // Escapes like `\p{Greek}` and `\x{e9}` take a braced argument.
+                if matches!(bytes.get(i + 1), Some(b'p') | Some(b'P') | Some(b'x') | Some(b'u'))
+                    && bytes.get(end) == Some(&b'{')
+                {
+                    end = pattern[end..].find('}').map_or(bytes.len(), |n| end + n + 1);
+                }
+                rw.copy(i..end);
+                i = end;
+                branch_start = false;
+            }
+            b'[' => {
+                let end = class_end(pattern, i);
+                rw.copy(i..end);
+                i = end;
+                branch_start = false;
+            }
+            b'(' => {
+                let end = if bytes.get(i + 1) == Some(&b'?') {
+                    pattern[i..]
+                        .find(&[':', ')', '>'][..])
+                        .map_or(bytes.len(), |n| i + n + 1)
+                } else {
+                    i + 1
+                };
+                // A flag group like `(?i)` doesn't start a new branch.
+                if bytes[end - 1] != b')' {
+                    branch_start = true;
+                    let named = pattern[i..].starts_with("(?P<");
+                    if bytes.get(i + 1) != Some(&b'?') || named {
+                        rw.captures += 1;
+                        groups.push(Group {
+                            name: named.then(|| String::from(&pattern[i + 4..end - 1])),
+                            index: rw.captures,
+                            closed: false,
+                        });
+                        parens.push(Some(groups.len() - 1));
+                    } else {
+                        parens.push(None);
+                    }
+                }
+                rw.copy(i..end);
+                i = end;
+            }
+            b')' => {
+                if let Some(Some(g)) = parens.pop() {
+                    groups[g].closed = true;
+                }
+                rw.copy(i..i + 1);
+                i += 1;
+                branch_start = false;
+            }
+            b'|' => {
+                rw.copy(i..i + 1);
+                i += 1;
+                branch_start = true;
+            }
+            b'{' if bytes.get(i + 1) == Some(&b'&') => {
+                let end = match pattern[i..].find('}') {
+                    Some(n) => i + n,
+                    None => return Err(rw.error(i..bytes.len(), "unclosed extension")),
+                };
+                let name = pattern[i + 2..end].trim();
+                if name.is_empty() {
+                    return Err(rw.error(i..end + 1, "expected the name of a word list"));
+                }
+                rw.marker("", Annotation::WordList(String::from(name)), i);
+                i = end + 1;
+                branch_start = false;
+            }
+            b'#' if !rules.is_empty() => {
+                let end = match pattern[i + 1..].find('#') {
+                    Some(n) => i + 1 + n,
+                    None => return Err(rw.error(i..bytes.len(), "unclosed rule reference")),
+                };
+                let name = &pattern[i + 1..end];
+                let rule = match rules.iter().position(|r| r == name) {
+                    Some(rule) => rule,
+                    None if name.is_empty() => {
+                        return Err(rw.error(i..end + 1, "expected the name of a rule"))
+                    }
+                    None => {
+                        return Err(rw.error(
+                            i..end + 1,
+                            &format!("there's no rule named `{}`", name),
+                        ))
+                    }
+                };
+                rw.marker("", Annotation::Rule(rule), i);
+                i = end + 1;
+                branch_start = false;
+            }
+            b'{' if matches!(bytes.get(i + 1), Some(b'=') | Some(b'?')) => {
+                let end = match pattern[i..].find('}') {
+                    Some(n) => i + n,
+                    None => return Err(rw.error(i..bytes.len(), "unclosed extension")),
+                };
+                let value = match pattern[i + 2..end].trim().parse::<f64>() {
+                    Ok(v) if v.is_finite() => v,
+                    _ => return Err(rw.error(i + 2..end, "expected a number")),
+                };
+                if bytes[i + 1] == b'=' {
+                    if !branch_start {
+                        return Err(rw.error(
+                            i..end + 1,
+                            "a weight must be at the start of an alternation branch",
+                        ));
+                    }
+                    if value < 0.0 {
+                        return Err(rw.error(i + 2..end, "a weight can't be negative"));
+                    }
+                    rw.marker("", Annotation::Weight(value), i);
+                } else {
+                    if branch_start {
+                        return Err(rw.error(i..end + 1, "a probability must follow an expression"));
+                    }
+                    if was_quantifier {
+                        return Err(rw.error(
+                            i..end + 1,
+                            "a probability can't follow another quantifier",
+                        ));
+                    }
+                    if !(0.0..=1.0).contains(&value) {
+                        return Err(rw.error(i + 2..end, "a probability must be between 0 and 1"));
+                    }
+                    rw.marker("?", Annotation::Probability(value), i);
+                    after_probability = true;
+                }
+                i = end + 1;
+            }
+            b'?' | b'*' | b'+' | b'{' if was_probability => {
+                return Err(rw.error(i..i + 1, "a probability can't be repeated"));
+            }
+            b'?' | b'*' | b'+' => {
+                rw.copy(i..i + 1);
+                i += 1;
+                quantifier = true;
+            }
+            b'{' => {
+                let end = pattern[i..].find('}').map_or(bytes.len(), |n| i + n + 1);
+                rw.copy(i..end);
+                i = end;
+                quantifier = true;
+            }
+            _ => {
+                let len = pattern[i..].chars().next().map_or(1, char::len_utf8);
+                rw.copy(i..i + len);
+                i += len;
+                branch_start = false;
+            }
+        }
+    }
+    if let Some((span, reference)) = unresolved.into_iter().next() {
+        let message = if reference.find(&groups).is_some() {
+            format!("a back-reference can't refer to a later group ({})", reference)
+        } else {
+            format!("there's no {} to refer back to", reference)
+        };
+        return Err(rw.error(span, &message));
+    }
+    rw.out.offsets.push(pattern.len());
+    Ok(rw.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preprocess_test() {
+        let ext = preprocess("({=4}a|b{?0.25}|{=0.5}[{=]c\\p{L}{2})", &[]).unwrap();
+        assert_eq!(
+            ext.pattern,
+            "((?P<__ss0>)a|b?(?P<__ss1>)|(?P<__ss2>)[{=]c\\p{L}{2})"
+        );
+        assert_eq!(
+            ext.annotations,
+            vec![
+                Annotation::Weight(4.0),
+                Annotation::Probability(0.25),
+                Annotation::Weight(0.5),
+            ]
+        );
+        assert_eq!(marker_index("__ss2"), Some(2));
+        assert_eq!(marker_index("name"), None);
+    }
+
+    #[test]
+    fn preprocess_backref_test() {
+        let ext = preprocess("(a)(?:b)(?P<x>{=2}c|d)\\1\\k<x>", &[]).unwrap();
+        assert_eq!(
+            ext.pattern,
+            "(a)(?:b)(?P<x>(?P<__ss0>)c|d)(?P<__ss1>)(?P<__ss2>)"
+        );
+        assert_eq!(
+            ext.annotations,
+            vec![
+                Annotation::Weight(2.0),
+                Annotation::BackRef(1),
+                Annotation::BackRef(2),
+            ]
+        );
+        assert_eq!(
+            scan_groups("(a)[(]\\((?:b)(?P<c>\\12)"),
+            vec![
+                GroupToken::Open,
+                GroupToken::Open,
+                GroupToken::Reference {
+                    number: 12,
+                    span: 19..22
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn preprocess_rule_test() {
+        let rules = [String::from("a"), String::from("b")];
+        let ext = preprocess(r"(#a#|#b#)[#][^#]\#", &rules).unwrap();
+        assert_eq!(ext.pattern, r"((?P<__ss0>)|(?P<__ss1>))[#][^#]\#");
+        assert_eq!(ext.annotations, vec![Annotation::Rule(0), Annotation::Rule(1)]);
+        assert_eq!(preprocess("a#b", &[]).unwrap().pattern, "a#b");
+        for (pattern, span) in &[("x#c#", 1..4), ("#a", 0..2), ("##", 0..2)] {
+            match preprocess(pattern, &rules) {
+                Err(e) => assert_eq!(e.location(), Some((*pattern, span.clone())), "{}", pattern),
+                Ok(_) => panic!("expected an error for {}", pattern),
+            }
+        }
+    }
+
+    #[test]
+    fn preprocess_span_test() {
+        let ext = preprocess("{=2}ab(", &[]).unwrap();
+        let end = ext.pattern.len();
+        assert_eq!(ext.original_span(end - 1..end), 6..7);
+    }
+
+    #[test]
+    fn preprocess_error_test() {
+        for (pattern, span) in &[
+            ("a{=2}b", 1..5),
+            ("({?0.5}a)", 1..7),
+            ("a{?1.5}", 3..6),
+            ("a{=x}", 3..4),
+            ("(a|{=-1}b)", 5..7),
+            ("a{?0.5}*", 7..8),
+            ("a*{?0.5}", 2..8),
+            ("a{2}{?0.5}", 4..10),
+            ("(a)\\2", 3..5),
+            ("\\1(a)", 0..2),
+            ("(a\\1)", 2..4),
+            ("(?P<x>a)\\k<y>", 8..13),
+            ("(a)\\0", 3..5),
+        ] {
+            match preprocess(pattern, &[]) {
+                Err(e) => assert_eq!(e.location(), Some((*pattern, span.clone())), "{}", pattern),
+                Ok(_) => panic!("expected an error for {:?}", pattern),
+            }
+        }
+    }
+}
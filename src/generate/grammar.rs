@@ -0,0 +1,284 @@
+//! Generating strings from a grammar of rules that expand into each other.
+
+use rand::RngCore;
+
+use super::alias::AliasTable;
+use super::backend::Generator;
+use super::regex_gen::{ExpandRule, Record, RegexGen, RegexGenOptions};
+use crate::config::{Config, Grammar};
+use crate::error::Error;
+
+struct CompiledRule {
+    /// Each alternative's pattern, with its `#rule#` references marked.
+    alternatives: Vec<RegexGen>,
+    weights: Vec<f64>,
+    table: AliasTable,
+    /// Fewest levels of rules it takes to finish each alternative, with
+    /// `u32::MAX` for never.
+    heights: Vec<u32>,
+}
+
+/// Generates strings from a `Grammar`.
+pub struct GrammarGen {
+    name: String,
+    rules: Vec<CompiledRule>,
+    start: usize,
+    max_depth: u32,
+}
+
+impl GrammarGen {
+    /// Compile `grammar`, generating its patterns, assembled from `config`'s
+    /// fragments, with `options`.
+    pub fn new(
+        grammar: &Grammar,
+        config: &Config,
+        options: &RegexGenOptions,
+    ) -> Result<Self, Error> {
+        let invalid =
+            |message: String| Error::Config(format!("Grammar `{}`: {}", grammar.name, message));
+        let index_of = |name: &str| {
+            grammar
+                .rules
+                .iter()
+                .position(|r| r.name == name)
+                .ok_or_else(|| invalid(format!("there's no rule named `{}`", name)))
+        };
+        let names: Vec<String> = grammar.rules.iter().map(|r| r.name.clone()).collect();
+        let mut rules = Vec::with_capacity(grammar.rules.len());
+        for rule in &grammar.rules {
+            let choices: Vec<(&_, f64)> = if rule.alternatives.is_empty() {
+                vec![(&rule.pattern, 1.0)]
+            } else {
+                rule.alternatives
+                    .iter()
+                    .map(|a| (&a.pattern, a.weight))
+                    .collect()
+            };
+            let mut alternatives = Vec::with_capacity(choices.len());
+            for (pattern, _) in &choices {
+                let options = RegexGenOptions {
+                    rules: names.clone(),
+                    ..options.clone()
+                };
+                let (pattern, options) = super::assemble_options(config, pattern, options)?;
+                alternatives.push(RegexGen::with_options(&pattern, &options)?);
+            }
+            let weights: Vec<f64> = choices.iter().map(|(_, w)| *w).collect();
+            let table = AliasTable::new(&weights).ok_or_else(|| {
+                invalid(format!(
+                    "rule `{}` needs an alternative with a weight above 0",
+                    rule.name
+                ))
+            })?;
+            rules.push(CompiledRule {
+                heights: vec![u32::MAX; alternatives.len()],
+                alternatives,
+                weights,
+                table,
+            });
+        }
+        let start = index_of(&grammar.start)?;
+        let mut gen = Self {
+            name: grammar.name.clone(),
+            rules,
+            start,
+            max_depth: grammar.max_depth,
+        };
+        gen.compute_heights();
+        if let Some(i) = (0..gen.rules.len()).find(|i| gen.height(*i) == u32::MAX) {
+            return Err(invalid(format!(
+                "rule `{}` can never finish, since every alternative leads back to it",
+                grammar.rules[i].name
+            )));
+        }
+        if gen.height(start) > gen.max_depth {
+            return Err(invalid(format!(
+                "rule `{}` needs a `max_depth` of at least {}",
+                grammar.start,
+                gen.height(start)
+            )));
+        }
+        Ok(gen)
+    }
+
+    /// Fewest levels of rules it takes to finish rule `i`.
+    fn height(&self, i: usize) -> u32 {
+        self.rules[i]
+            .heights
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Work out the heights of every alternative, which depend on each
+    /// other, by lowering them from never until nothing changes.
+    ///
+    /// An alternative counts as needing every rule it refers to, even those
+    /// it might skip, so any way of generating it fits within its height.
+    fn compute_heights(&mut self) {
+        loop {
+            let mut changed = false;
+            for r in 0..self.rules.len() {
+                for a in 0..self.rules[r].alternatives.len() {
+                    let height = self.rules[r].alternatives[a]
+                        .rule_refs()
+                        .map(|i| self.height(i))
+                        .max()
+                        .unwrap_or(0)
+                        .saturating_add(1);
+                    if height < self.rules[r].heights[a] {
+                        self.rules[r].heights[a] = height;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Append an expansion of rule `i` to `out`, with `depth` levels of
+    /// rules left, which is always enough to finish it.
+    fn expand(
+        &self,
+        i: usize,
+        depth: u32,
+        rng: &mut dyn RngCore,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        let rule = &self.rules[i];
+        let a = if rule.heights.iter().all(|h| *h <= depth) {
+            rule.table.sample(rng)
+        } else {
+            // Deep down, leave out the alternatives that can't finish in time.
+            let weights: Vec<f64> = rule
+                .weights
+                .iter()
+                .zip(&rule.heights)
+                .map(|(w, h)| if *h <= depth { *w } else { 0.0 })
+                .collect();
+            match AliasTable::new(&weights) {
+                Some(table) => table.sample(rng),
+                // Every alternative that can finish has a weight of 0.
+                None => rule.heights.iter().position(|h| *h <= depth).unwrap(),
+            }
+        };
+        let inner = Expansion {
+            gen: self,
+            depth: depth - 1,
+        };
+        let record = rule.alternatives[a].randomize_with_rules(&mut &mut *rng, &inner)?;
+        *out += &record.text;
+        Ok(())
+    }
+}
+
+/// Expands the rules referred to at one level of a grammar.
+struct Expansion<'a> {
+    gen: &'a GrammarGen,
+    /// Levels of rules left.
+    depth: u32,
+}
+
+impl ExpandRule for Expansion<'_> {
+    fn expand_rule(
+        &self,
+        rule: usize,
+        rng: &mut dyn RngCore,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        self.gen.expand(rule, self.depth, rng, out)
+    }
+}
+
+impl Generator for GrammarGen {
+    fn generate_record(&self, rng: &mut dyn RngCore) -> Result<Record, Error> {
+        let mut text = String::new();
+        self.expand(self.start, self.max_depth, rng, &mut text)?;
+        Ok(Record::from(text))
+    }
+
+    fn pattern(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The grammar backend, for the configured grammar.
+pub(crate) fn grammar_generator(config: &Config) -> Result<Box<dyn Generator>, Error> {
+    let grammar = match config.grammar()? {
+        Some(grammar) => grammar,
+        None => {
+            return Err(Error::Config(String::from(
+                "The `grammar` backend needs a grammar; pass one with `--grammar`",
+            )))
+        }
+    };
+    // Length limits apply to the whole string, so only the maximum is any
+    // use to each pattern.
+    let options = RegexGenOptions {
+        min_len: None,
+        ..super::regex_gen_options(config)?
+    };
+    Ok(Box::new(GrammarGen::new(&grammar, config, &options)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rule;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn grammar_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let config = Config::default();
+        let options = RegexGenOptions::default();
+        let place = Grammar::builtins().remove(0);
+        let gen = GrammarGen::new(&place, &config, &options).unwrap();
+        for _ in 0..100 {
+            let s = gen.generate(&mut rng).unwrap();
+            assert!(!s.contains('#'), "{}", s);
+        }
+
+        // Nesting brackets deeper than the limit would allow must stop.
+        let mut nested = Grammar::new(
+            "nested",
+            vec![Rule::with_alternatives(
+                "start",
+                &[(r"\(#start#\)", 10.0), ("x", 1.0)],
+            )],
+        );
+        nested.max_depth = 4;
+        let gen = GrammarGen::new(&nested, &config, &options).unwrap();
+        for _ in 0..100 {
+            let s = gen.generate(&mut rng).unwrap();
+            let n = s.matches('(').count();
+            assert!(n <= 3, "{}", s);
+            assert_eq!(s, format!("{}x{}", "(".repeat(n), ")".repeat(n)));
+        }
+
+        // References can sit anywhere in a pattern, and `#` in a class is a
+        // literal.
+        let choice = Grammar::new(
+            "choice",
+            vec![
+                Rule::new("start", "(#a#|#b#)[#]"),
+                Rule::new("a", "x"),
+                Rule::new("b", r"[^#\s]{?0}y"),
+            ],
+        );
+        let gen = GrammarGen::new(&choice, &config, &options).unwrap();
+        let mut strings: Vec<String> = (0..50).map(|_| gen.generate(&mut rng).unwrap()).collect();
+        strings.sort();
+        strings.dedup();
+        assert_eq!(strings, vec!["x#", "y#"]);
+
+        let forever = Grammar::new("forever", vec![Rule::new("start", "a#start#")]);
+        assert!(GrammarGen::new(&forever, &config, &options).is_err());
+        let missing = Grammar::new("missing", vec![Rule::new("start", "#nope#")]);
+        assert!(GrammarGen::new(&missing, &config, &options).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Generating words from a character Markov model trained on example words.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::alias::AliasTable;
+use super::backend::{Generator, MAX_ATTEMPTS};
+use super::regex_gen::Record;
+use crate::config::{Config, LengthUnit};
+use crate::error::Error;
+
+/// Pads the start of a word's context and marks its end.
+const BOUNDARY: char = '\0';
+
+/// How often each character follows each run of `order` characters in a set
+/// of training words.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarkovModel {
+    pub order: usize,
+    /// The characters seen after each context and how many times, with the
+    /// end of a word as `\0`.
+    transitions: BTreeMap<String, BTreeMap<char, u32>>,
+    /// The training words, for telling new words apart from them.
+    words: BTreeSet<String>,
+}
+
+impl MarkovModel {
+    /// Count the transitions in `words`, ignoring blank ones.
+    pub fn train<'a>(words: impl IntoIterator<Item = &'a str>, order: usize) -> Result<Self, Error> {
+        if order == 0 {
+            return Err(Error::Config(String::from(
+                "A Markov model needs an order of at least 1",
+            )));
+        }
+        let mut model = Self {
+            order,
+            transitions: BTreeMap::new(),
+            words: BTreeSet::new(),
+        };
+        for word in words {
+            let word: String = word.trim().chars().filter(|c| !c.is_control()).collect();
+            if word.is_empty() {
+                continue;
+            }
+            let mut context = model.start();
+            for c in word.chars().chain(Some(BOUNDARY)) {
+                *model
+                    .transitions
+                    .entry(context.clone())
+                    .or_default()
+                    .entry(c)
+                    .or_insert(0) += 1;
+                context = shift(&context, c);
+            }
+            model.words.insert(word);
+        }
+        if model.words.is_empty() {
+            return Err(Error::Config(String::from(
+                "A Markov model needs at least one word to train on",
+            )));
+        }
+        Ok(model)
+    }
+
+    /// Train on the lines of a word list.
+    pub fn train_file(path: &Path, order: usize) -> Result<Self, Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(source) => {
+                return Err(Error::DataIo {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        Self::train(text.lines(), order)
+    }
+
+    /// Read a model saved with `save`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(source) => {
+                return Err(Error::DataIo {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        serde_json::from_str(&text).map_err(|source| Error::ModelJson {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Write the model to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let io_error = |source| Error::DataIo {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let text = serde_json::to_string(self).map_err(|source| Error::ModelJson {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        std::fs::write(path, text).map_err(io_error)
+    }
+
+    /// Number of words the model was trained on.
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Whether `word` was one of the training words.
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    /// The words the model was trained on.
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(String::as_str)
+    }
+
+    /// Number of different characters that can follow a context, counting
+    /// the end of a word.
+    pub fn alphabet_size(&self) -> usize {
+        let chars: BTreeSet<char> = self.transitions.values().flat_map(|n| n.keys()).copied().collect();
+        chars.len()
+    }
+
+    /// Mean log probability of each step of `word`, including its end, with
+    /// every count raised by one over an alphabet of `alphabet` characters so
+    /// unseen steps aren't impossible.
+    pub fn log_likelihood(&self, word: &str, alphabet: usize) -> f64 {
+        let mut context = self.start();
+        let mut total = 0.0;
+        let mut steps = 0;
+        for c in word.chars().chain(Some(BOUNDARY)) {
+            let (seen, count) = match self.transitions.get(&context) {
+                Some(next) => (next.values().sum::<u32>(), next.get(&c).copied().unwrap_or(0)),
+                None => (0, 0),
+            };
+            total += ((count + 1) as f64 / (seen as usize + alphabet) as f64).ln();
+            steps += 1;
+            context = shift(&context, c);
+        }
+        total / steps as f64
+    }
+
+    /// The context at the start of a word.
+    fn start(&self) -> String {
+        std::iter::repeat(BOUNDARY).take(self.order).collect()
+    }
+}
+
+/// `context` with its first character dropped and `c` added.
+fn shift(context: &str, c: char) -> String {
+    context.chars().skip(1).chain(Some(c)).collect()
+}
+
+/// Generates words from a `MarkovModel`.
+pub struct MarkovGen {
+    model: MarkovModel,
+    /// Where the model was loaded from, for error messages.
+    name: String,
+    /// The characters that can follow each context and how to pick one.
+    tables: HashMap<String, (Vec<char>, AliasTable)>,
+    min_len: usize,
+    max_len: usize,
+    /// Whether to leave out the training words.
+    novel: bool,
+}
+
+impl MarkovGen {
+    /// Generate words of `min_len` to `max_len` characters from `model`,
+    /// leaving out its training words if `novel` is set.
+    pub fn new(
+        model: MarkovModel,
+        name: &str,
+        min_len: Option<usize>,
+        max_len: Option<usize>,
+        novel: bool,
+    ) -> Self {
+        let tables = model
+            .transitions
+            .iter()
+            .filter_map(|(context, next)| {
+                let weights: Vec<f64> = next.values().map(|n| *n as f64).collect();
+                let table = AliasTable::new(&weights)?;
+                Some((context.clone(), (next.keys().copied().collect(), table)))
+            })
+            .collect();
+        Self {
+            model,
+            name: String::from(name),
+            tables,
+            min_len: min_len.unwrap_or(1),
+            max_len: max_len.unwrap_or(usize::MAX),
+            novel,
+        }
+    }
+
+    /// Walk the model from the start of a word to its end, or return `None`
+    /// once it's longer than `max_len`.
+    fn walk(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let mut word = String::new();
+        let mut len = 0;
+        let mut context = self.model.start();
+        // Every context the walk can reach was seen in training, and so has
+        // a successor.
+        while let Some((chars, table)) = self.tables.get(&context) {
+            let c = chars[table.sample(rng)];
+            if c == BOUNDARY {
+                return Some(word);
+            }
+            len += 1;
+            if len > self.max_len {
+                return None;
+            }
+            word.push(c);
+            context = shift(&context, c);
+        }
+        None
+    }
+}
+
+impl Generator for MarkovGen {
+    fn generate_record(&self, rng: &mut dyn RngCore) -> Result<Record, Error> {
+        let word = match self.walk(rng) {
+            Some(word) if word.chars().count() >= self.min_len => word,
+            _ => {
+                return Err(Error::Miss(Box::new(Error::Length {
+                    pattern: self.name.clone(),
+                    min_len: Some(self.min_len),
+                    max_len: Some(self.max_len).filter(|max| *max != usize::MAX),
+                    unit: LengthUnit::Chars,
+                })))
+            }
+        };
+        if self.novel && self.model.contains(&word) {
+            return Err(Error::Miss(Box::new(Error::NotNovel {
+                model: self.name.clone(),
+                attempts: MAX_ATTEMPTS,
+            })));
+        }
+        Ok(Record::from(word))
+    }
+
+    fn pattern(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The Markov backend, for the configured model.
+pub(crate) fn markov_generator(config: &Config) -> Result<Box<dyn Generator>, Error> {
+    let path = match &config.model {
+        Some(path) => config.resolve_path(path),
+        None => {
+            return Err(Error::Config(String::from(
+                "The `markov` backend needs a model; train one with `train` and pass it with `--model`",
+            )))
+        }
+    };
+    let model = MarkovModel::load(&path)?;
+    let (min_len, max_len) = super::length_hints(config);
+    Ok(Box::new(MarkovGen::new(
+        model,
+        &path.display().to_string(),
+        min_len,
+        max_len,
+        config.novel,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    const WORDS: &[&str] = &[
+        "aragorn", "arwen", "boromir", "celeborn", "elrond", "eowyn", "faramir", "galadriel",
+        "gimli", "legolas", "thranduil", "elendil", "isildur", "anarion",
+    ];
+
+    #[test]
+    fn markov_test() {
+        let model = MarkovModel::train(WORDS.iter().copied(), 2).unwrap();
+        assert_eq!(model.word_count(), WORDS.len());
+        let json = serde_json::to_string(&model).unwrap();
+        assert_eq!(serde_json::from_str::<MarkovModel>(&json).unwrap(), model);
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let gen = MarkovGen::new(model.clone(), "test", Some(4), Some(8), true);
+        for _ in 0..100 {
+            let word = gen.generate(&mut rng).unwrap();
+            assert!((4..=8).contains(&word.chars().count()), "{}", word);
+            assert!(!model.contains(&word), "{}", word);
+        }
+
+        // An order high enough to only ever reproduce the training words.
+        let model = MarkovModel::train(["abcdef"], 3).unwrap();
+        let gen = MarkovGen::new(model, "test", None, None, true);
+        match gen.generate(&mut rng) {
+            Err(Error::NotNovel { .. }) => {}
+            r => panic!("expected a novelty error, got {:?}", r),
+        }
+        assert!(MarkovModel::train(["", "  "], 2).is_err());
+        assert!(MarkovModel::train(["abc"], 0).is_err());
+    }
+}
@@ -0,0 +1,579 @@
+use std::collections::{HashMap, HashSet};
+
+use regex_syntax::hir::{self, Hir, HirKind};
+
+use super::alias::AliasTable;
+use super::dfa::interval_len;
+use super::extension::{marker_index, Annotation};
+use super::regex_gen::Source;
+use crate::config::{Frequencies, WordList};
+use crate::error::Error;
+
+/// A zero-width assertion that must hold at a position of the output.
+#[derive(Debug, Clone)]
+pub enum Assertion {
+    Anchor(hir::Anchor),
+    WordBoundary(hir::WordBoundary),
+}
+
+impl Assertion {
+    /// Whether the assertion holds between the characters `before` and `after`.
+    pub fn holds(&self, before: Option<char>, after: Option<char>) -> bool {
+        match self {
+            Self::Anchor(hir::Anchor::StartText) => before.is_none(),
+            Self::Anchor(hir::Anchor::EndText) => after.is_none(),
+            Self::Anchor(hir::Anchor::StartLine) => before.is_none() || before == Some('\n'),
+            Self::Anchor(hir::Anchor::EndLine) => after.is_none() || after == Some('\n'),
+            Self::WordBoundary(wb) => {
+                let ascii = matches!(
+                    wb,
+                    hir::WordBoundary::Ascii | hir::WordBoundary::AsciiNegate
+                );
+                let boundary = is_word_char(before, ascii) != is_word_char(after, ascii);
+                match wb {
+                    hir::WordBoundary::Unicode | hir::WordBoundary::Ascii => boundary,
+                    hir::WordBoundary::UnicodeNegate | hir::WordBoundary::AsciiNegate => !boundary,
+                }
+            }
+        }
+    }
+}
+
+fn is_word_char(c: Option<char>, ascii: bool) -> bool {
+    match c {
+        Some(c) if ascii => c.is_ascii() && regex_syntax::is_word_byte(c as u8),
+        Some(c) => regex_syntax::is_word_character(c),
+        None => false,
+    }
+}
+
+/// A pattern compiled from its HIR, with the syntax extensions resolved and
+/// the tables needed for sampling built once up front.
+#[derive(Debug, Clone)]
+pub enum Node {
+    Empty,
+    Literal(char),
+    Class(hir::ClassUnicode),
+    Concat(Vec<Node>),
+    Alternation {
+        branches: Vec<Node>,
+        weights: Vec<f64>,
+        table: AliasTable,
+    },
+    Repetition {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        /// Chance of a `?` repetition being taken, if not even.
+        probability: Option<f64>,
+    },
+    Group(Box<Node>),
+    /// A capture group that is named or referred back to, whose text gets
+    /// recorded.
+    Capture {
+        /// Capture index in the rewritten pattern.
+        index: u32,
+        name: Option<String>,
+        node: Box<Node>,
+    },
+    /// A back-reference to the capture group with `index`, which generates
+    /// the same text the group last did, or nothing if it wasn't used.
+    BackRef {
+        index: u32,
+        /// A copy of the group's contents, for working out what the
+        /// back-reference can generate.
+        node: Box<Node>,
+    },
+    Assertion(Assertion),
+    /// An expansion of the grammar rule with this index, which can be any
+    /// length.
+    Rule(usize),
+    /// A string from the source with this index, generated by another
+    /// backend, which can be any length.
+    Source(usize),
+}
+
+impl Node {
+    /// Repetition counts that can actually be generated, taking `{?0}` and
+    /// `{?1}` into account. `None` means unbounded.
+    pub fn repeat_range(min: u32, max: Option<u32>, probability: Option<f64>) -> (u32, Option<u32>) {
+        match probability {
+            Some(p) if p <= 0.0 => (min, Some(min)),
+            Some(p) if p >= 1.0 => (max.unwrap_or(min), max),
+            _ => (min, max),
+        }
+    }
+
+    /// The branches of an alternation that can be picked, i.e. those with a
+    /// positive weight.
+    pub fn live_branches<'a>(
+        branches: &'a [Node],
+        weights: &'a [f64],
+    ) -> impl Iterator<Item = &'a Node> + 'a {
+        branches
+            .iter()
+            .zip(weights)
+            .filter(|(_, w)| **w > 0.0)
+            .map(|(n, _)| n)
+    }
+
+    /// The fewest characters this node can generate.
+    pub fn min_len(&self) -> usize {
+        match self {
+            Self::Empty
+            | Self::Assertion(_)
+            | Self::BackRef { .. }
+            | Self::Rule(_)
+            | Self::Source(_) => 0,
+            Self::Literal(_) | Self::Class(_) => 1,
+            Self::Concat(nodes) => nodes.iter().map(Node::min_len).sum(),
+            Self::Alternation {
+                branches, weights, ..
+            } => Self::live_branches(branches, weights)
+                .map(Node::min_len)
+                .min()
+                .unwrap_or(0),
+            Self::Repetition {
+                node,
+                min,
+                max,
+                probability,
+            } => {
+                let (min, _) = Self::repeat_range(*min, *max, *probability);
+                node.min_len().saturating_mul(min as usize)
+            }
+            Self::Group(node) | Self::Capture { node, .. } => node.min_len(),
+        }
+    }
+
+    /// The most characters this node can generate, or `None` if unbounded.
+    pub fn max_len(&self) -> Option<usize> {
+        match self {
+            Self::Empty | Self::Assertion(_) => Some(0),
+            Self::Literal(_) | Self::Class(_) => Some(1),
+            Self::Rule(_) | Self::Source(_) => None,
+            Self::Concat(nodes) => nodes
+                .iter()
+                .try_fold(0usize, |acc, n| Some(acc.saturating_add(n.max_len()?))),
+            Self::Alternation {
+                branches, weights, ..
+            } => Self::live_branches(branches, weights)
+                .try_fold(0usize, |acc, n| Some(acc.max(n.max_len()?))),
+            Self::Repetition {
+                node,
+                min,
+                max,
+                probability,
+            } => {
+                let (_, max) = Self::repeat_range(*min, *max, *probability);
+                match (node.max_len()?, max) {
+                    (0, _) => Some(0),
+                    (len, Some(max)) => Some(len.saturating_mul(max as usize)),
+                    (_, None) => None,
+                }
+            }
+            Self::Group(node) | Self::Capture { node, .. } | Self::BackRef { node, .. } => {
+                node.max_len()
+            }
+        }
+    }
+
+    /// Whether the node contains any anchors or word boundaries.
+    pub fn has_assertions(&self) -> bool {
+        match self {
+            Self::Empty
+            | Self::Literal(_)
+            | Self::Class(_)
+            | Self::BackRef { .. }
+            | Self::Rule(_)
+            | Self::Source(_) => false,
+            Self::Assertion(_) => true,
+            Self::Concat(nodes) | Self::Alternation { branches: nodes, .. } => {
+                nodes.iter().any(Node::has_assertions)
+            }
+            Self::Repetition { node, .. } | Self::Group(node) | Self::Capture { node, .. } => {
+                node.has_assertions()
+            }
+        }
+    }
+
+    /// Whether the node contains any back-references.
+    pub fn has_backrefs(&self) -> bool {
+        match self {
+            Self::Empty
+            | Self::Literal(_)
+            | Self::Class(_)
+            | Self::Assertion(_)
+            | Self::Rule(_)
+            | Self::Source(_) => false,
+            Self::BackRef { .. } => true,
+            Self::Concat(nodes) | Self::Alternation { branches: nodes, .. } => {
+                nodes.iter().any(Node::has_backrefs)
+            }
+            Self::Repetition { node, .. } | Self::Group(node) | Self::Capture { node, .. } => {
+                node.has_backrefs()
+            }
+        }
+    }
+
+    /// Limit every class to the characters in `charset`, failing if a class
+    /// has none of them.
+    pub fn restrict_classes(
+        &mut self,
+        charset: &hir::ClassUnicode,
+        pattern: &str,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Class(cls) => {
+                cls.intersect(charset);
+                if cls.ranges().is_empty() {
+                    return Err(Error::Unsupported {
+                        pattern: String::from(pattern),
+                        kind: "a character class with nothing in the charset",
+                    });
+                }
+            }
+            Self::Concat(nodes) | Self::Alternation { branches: nodes, .. } => {
+                for n in nodes {
+                    n.restrict_classes(charset, pattern)?;
+                }
+            }
+            Self::Repetition { node, .. }
+            | Self::Group(node)
+            | Self::Capture { node, .. }
+            | Self::BackRef { node, .. } => node.restrict_classes(charset, pattern)?,
+            Self::Empty
+            | Self::Literal(_)
+            | Self::Assertion(_)
+            | Self::Rule(_)
+            | Self::Source(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Weight the characters of classes, and of alternations between single
+    /// characters, by `freq`.
+    pub fn apply_frequencies(&mut self, freq: &Frequencies) {
+        match self {
+            Self::Class(cls) => {
+                if let Some(node) = weighted_class(cls, freq) {
+                    *self = node;
+                }
+            }
+            Self::Alternation {
+                branches,
+                weights,
+                table,
+            } if branches.iter().all(|b| matches!(b, Self::Literal(_))) => {
+                let weighted: Vec<f64> = branches
+                    .iter()
+                    .zip(weights.iter())
+                    .map(|(b, w)| match b {
+                        Self::Literal(c) => w * freq.weight(*c),
+                        _ => *w,
+                    })
+                    .collect();
+                // Keep the branches as they were if the table rules them all out.
+                if let Some(t) = AliasTable::new(&weighted) {
+                    *weights = weighted;
+                    *table = t;
+                }
+            }
+            Self::Concat(nodes) | Self::Alternation { branches: nodes, .. } => {
+                for n in nodes {
+                    n.apply_frequencies(freq);
+                }
+            }
+            Self::Repetition { node, .. } | Self::Group(node) | Self::Capture { node, .. } => {
+                node.apply_frequencies(freq)
+            }
+            Self::Empty
+            | Self::Literal(_)
+            | Self::Assertion(_)
+            | Self::BackRef { .. }
+            | Self::Rule(_)
+            | Self::Source(_) => {}
+        }
+    }
+
+    /// Give each back-reference a copy of the group it refers to, from
+    /// `groups` and the groups before it.
+    fn link_backrefs(&mut self, groups: &mut HashMap<u32, Node>) {
+        match self {
+            Self::Concat(nodes) | Self::Alternation { branches: nodes, .. } => {
+                for n in nodes {
+                    n.link_backrefs(groups);
+                }
+            }
+            Self::Repetition { node, .. } | Self::Group(node) => node.link_backrefs(groups),
+            Self::Capture { index, node, .. } => {
+                node.link_backrefs(groups);
+                groups.insert(*index, (**node).clone());
+            }
+            Self::BackRef { index, node } => {
+                if let Some(group) = groups.get(index) {
+                    **node = group.clone();
+                }
+            }
+            Self::Empty
+            | Self::Literal(_)
+            | Self::Class(_)
+            | Self::Assertion(_)
+            | Self::Rule(_)
+            | Self::Source(_) => {}
+        }
+    }
+}
+
+fn class_contains(cls: &hir::ClassUnicode, c: char) -> bool {
+    let ranges = cls.ranges();
+    let i = ranges.partition_point(|r| r.end() < c);
+    i < ranges.len() && ranges[i].start() <= c
+}
+
+/// `cls` as an alternation between each of its characters in `freq`, weighted
+/// by the table, and the rest of the class, weighted by its size. `None` if
+/// the table has none of the class's characters.
+fn weighted_class(cls: &hir::ClassUnicode, freq: &Frequencies) -> Option<Node> {
+    let mut branches = Vec::new();
+    let mut weights = Vec::new();
+    let mut known = hir::ClassUnicode::new(vec![]);
+    for (c, w) in freq.iter().filter(|(c, _)| class_contains(cls, *c)) {
+        branches.push(Node::Literal(c));
+        weights.push(w);
+        known.push(hir::ClassUnicodeRange::new(c, c));
+    }
+    if branches.is_empty() {
+        return None;
+    }
+    let mut rest = cls.clone();
+    rest.difference(&known);
+    let size: u32 = rest
+        .iter()
+        .map(|r| interval_len(r.start() as u32, r.end() as u32))
+        .sum();
+    if size > 0 {
+        branches.push(Node::Class(rest));
+        weights.push(freq.default_weight() * size as f64);
+    }
+    let table = AliasTable::new(&weights)?;
+    Some(Node::Alternation {
+        branches,
+        weights,
+        table,
+    })
+}
+
+struct Compiler<'a> {
+    pattern: &'a str,
+    annotations: &'a [Annotation],
+    word_lists: &'a [WordList],
+    sources: &'a [Source],
+    /// Capture indices of the groups that back-references refer to.
+    referenced: HashSet<u32>,
+}
+
+impl<'a> Compiler<'a> {
+    fn unsupported(&self, kind: &'static str) -> Error {
+        Error::Unsupported {
+            pattern: String::from(self.pattern),
+            kind,
+        }
+    }
+
+    /// The annotation `hir` is a marker for, if it is one.
+    fn annotation(&self, hir: &Hir) -> Option<&'a Annotation> {
+        if let HirKind::Group(hir::Group {
+            kind: hir::GroupKind::CaptureName { name, .. },
+            ..
+        }) = hir.kind()
+        {
+            marker_index(name).and_then(|i| self.annotations.get(i))
+        } else {
+            None
+        }
+    }
+
+    /// An alternation between the entries of the word list `name`, or a
+    /// string from the source `name`.
+    fn word_list(&self, name: &str) -> Result<Node, Error> {
+        let list = match self.word_lists.iter().find(|l| l.name == name) {
+            Some(list) => list,
+            None => match self.sources.iter().position(|s| s.name == name) {
+                Some(source) => return Ok(Node::Source(source)),
+                None => return Err(Error::Config(format!("No word list named `{}`", name))),
+            },
+        };
+        let branches = list
+            .words
+            .iter()
+            .map(|w| {
+                let mut chars: Vec<Node> = w.chars().map(Node::Literal).collect();
+                if chars.len() == 1 {
+                    chars.pop().unwrap()
+                } else {
+                    Node::Concat(chars)
+                }
+            })
+            .collect();
+        let table = match AliasTable::new(&list.weights) {
+            Some(table) => table,
+            None => return Err(self.unsupported("a word list with no positive weight")),
+        };
+        Ok(Node::Alternation {
+            branches,
+            weights: list.weights.clone(),
+            table,
+        })
+    }
+
+    /// Split a leading weight marker off an alternation branch.
+    fn branch_weight(&self, hir: &'a Hir) -> Result<(f64, Node), Error> {
+        if let Some(Annotation::Weight(w)) = self.annotation(hir) {
+            return Ok((*w, Node::Empty));
+        }
+        if let HirKind::Concat(exprs) = hir.kind() {
+            if let Some(Annotation::Weight(w)) = exprs.first().and_then(|e| self.annotation(e)) {
+                return Ok((*w, self.compile_concat(&exprs[1..])?));
+            }
+        }
+        Ok((1.0, self.compile(hir)?))
+    }
+
+    fn compile_concat(&self, exprs: &'a [Hir]) -> Result<Node, Error> {
+        let mut nodes = Vec::with_capacity(exprs.len());
+        for e in exprs {
+            match self.annotation(e) {
+                Some(Annotation::Probability(p)) => match nodes.last_mut() {
+                    Some(Node::Repetition {
+                        probability,
+                        max: Some(1),
+                        ..
+                    }) => *probability = Some(*p),
+                    _ => return Err(self.unsupported("a probability without an expression")),
+                },
+                Some(Annotation::Weight(_)) => {}
+                Some(Annotation::BackRef(_))
+                | Some(Annotation::WordList(_))
+                | Some(Annotation::Rule(_))
+                | None => nodes.push(self.compile(e)?),
+            }
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.pop().unwrap()
+        } else {
+            Node::Concat(nodes)
+        })
+    }
+
+    fn compile(&self, hir: &'a Hir) -> Result<Node, Error> {
+        match self.annotation(hir) {
+            Some(Annotation::BackRef(index)) => {
+                return Ok(Node::BackRef {
+                    index: *index,
+                    node: Box::new(Node::Empty),
+                })
+            }
+            Some(Annotation::WordList(name)) => return self.word_list(name),
+            Some(Annotation::Rule(rule)) => return Ok(Node::Rule(*rule)),
+            Some(_) => return Ok(Node::Empty),
+            None => {}
+        }
+        Ok(match hir.kind() {
+            HirKind::Empty => Node::Empty,
+            HirKind::Literal(hir::Literal::Unicode(c)) => Node::Literal(*c),
+            HirKind::Literal(hir::Literal::Byte(_)) => return Err(self.unsupported("a byte literal")),
+            HirKind::Class(hir::Class::Unicode(cls)) => {
+                if cls.ranges().is_empty() {
+                    return Err(self.unsupported("an empty character class"));
+                }
+                Node::Class(cls.clone())
+            }
+            HirKind::Class(hir::Class::Bytes(_)) => return Err(self.unsupported("a byte class")),
+            HirKind::Anchor(a) => Node::Assertion(Assertion::Anchor(a.clone())),
+            HirKind::WordBoundary(wb) => Node::Assertion(Assertion::WordBoundary(wb.clone())),
+            HirKind::Group(hir::Group {
+                kind: hir::GroupKind::CaptureName { name, index },
+                hir,
+            }) => Node::Capture {
+                index: *index,
+                name: Some(name.clone()),
+                node: Box::new(self.compile(hir)?),
+            },
+            HirKind::Group(hir::Group {
+                kind: hir::GroupKind::CaptureIndex(index),
+                hir,
+            }) if self.referenced.contains(index) => Node::Capture {
+                index: *index,
+                name: None,
+                node: Box::new(self.compile(hir)?),
+            },
+            HirKind::Group(group) => Node::Group(Box::new(self.compile(&group.hir)?)),
+            HirKind::Concat(exprs) => self.compile_concat(exprs)?,
+            HirKind::Alternation(exprs) => {
+                let mut branches = Vec::with_capacity(exprs.len());
+                let mut weights = Vec::with_capacity(exprs.len());
+                for e in exprs {
+                    let (w, node) = self.branch_weight(e)?;
+                    weights.push(w);
+                    branches.push(node);
+                }
+                let table = match AliasTable::new(&weights) {
+                    Some(table) => table,
+                    None => return Err(self.unsupported("an alternation with no positive weight")),
+                };
+                Node::Alternation {
+                    branches,
+                    weights,
+                    table,
+                }
+            }
+            HirKind::Repetition(rep) => {
+                let (min, max) = match &rep.kind {
+                    hir::RepetitionKind::ZeroOrOne => (0, Some(1)),
+                    hir::RepetitionKind::ZeroOrMore => (0, None),
+                    hir::RepetitionKind::OneOrMore => (1, None),
+                    hir::RepetitionKind::Range(hir::RepetitionRange::Exactly(n)) => (*n, Some(*n)),
+                    hir::RepetitionKind::Range(hir::RepetitionRange::AtLeast(n)) => (*n, None),
+                    hir::RepetitionKind::Range(hir::RepetitionRange::Bounded(m, n)) => {
+                        (*m, Some(*n))
+                    }
+                };
+                Node::Repetition {
+                    node: Box::new(self.compile(&rep.hir)?),
+                    min,
+                    max,
+                    probability: None,
+                }
+            }
+        })
+    }
+}
+
+/// Compile `hir`, parsed from `pattern` with the extensions rewritten into
+/// markers for `annotations`, which can refer to `word_lists` and `sources`.
+pub fn compile(
+    hir: &Hir,
+    pattern: &str,
+    annotations: &[Annotation],
+    word_lists: &[WordList],
+    sources: &[Source],
+) -> Result<Node, Error> {
+    let referenced = annotations
+        .iter()
+        .filter_map(|a| match a {
+            Annotation::BackRef(index) => Some(*index),
+            _ => None,
+        })
+        .collect();
+    let mut root = Compiler {
+        pattern,
+        annotations,
+        word_lists,
+        sources,
+        referenced,
+    }
+    .compile(hir)?;
+    root.link_backrefs(&mut HashMap::new());
+    Ok(root)
+}
@@ -0,0 +1,233 @@
+//! Building pronounceable words syllable by syllable from a `Phonology`.
+
+use std::collections::BTreeMap;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::RngCore;
+
+use super::alias::AliasTable;
+use super::backend::Generator;
+use super::regex_gen::Record;
+use crate::config::{Config, Phonology};
+use crate::error::Error;
+
+/// Sounds that can fill one part of a syllable, and how to pick one.
+struct Inventory {
+    sounds: Vec<String>,
+    weights: Vec<f64>,
+    table: AliasTable,
+}
+
+impl Inventory {
+    /// The inventory of `entries`, which are the `part` of phonology `name`.
+    fn new(name: &str, part: &str, entries: &BTreeMap<String, f64>) -> Result<Self, Error> {
+        let invalid =
+            |message: &str| Error::Config(format!("Phonology `{}` needs {} {}", name, part, message));
+        if entries.values().any(|w| !(*w >= 0.0 && w.is_finite())) {
+            return Err(invalid("with weights of at least 0"));
+        }
+        if part == "nuclei" && entries.keys().any(String::is_empty) {
+            return Err(invalid("that aren't empty"));
+        }
+        let weights: Vec<f64> = entries.values().copied().collect();
+        let table = AliasTable::new(&weights).ok_or_else(|| invalid("with a weight above 0"))?;
+        Ok(Self {
+            sounds: entries.keys().cloned().collect(),
+            weights,
+            table,
+        })
+    }
+}
+
+/// A sequence no word may contain.
+struct Forbidden {
+    text: String,
+    /// Only forbidden at the start of a word.
+    start: bool,
+    /// Only forbidden at the end of a word.
+    end: bool,
+}
+
+impl Forbidden {
+    fn new(s: &str) -> Self {
+        let start = s.starts_with('^');
+        let text = s.strip_prefix('^').unwrap_or(s);
+        let end = text.ends_with('$');
+        let text = text.strip_suffix('$').unwrap_or(text);
+        Self {
+            text: String::from(text),
+            start,
+            end,
+        }
+    }
+
+    /// Whether `word` breaks the rule, where `finished` says whether it's
+    /// the whole word or only the start of one.
+    fn broken_by(&self, word: &str, finished: bool) -> bool {
+        match (self.start, self.end) {
+            (false, false) => word.contains(&self.text),
+            (true, false) => word.starts_with(&self.text),
+            (false, true) => finished && word.ends_with(&self.text),
+            (true, true) => finished && word == self.text,
+        }
+    }
+}
+
+/// Generates words from a `Phonology`.
+pub struct PhonologyGen {
+    name: String,
+    onsets: Inventory,
+    nuclei: Inventory,
+    codas: Inventory,
+    forbidden: Vec<Forbidden>,
+    syllables: Uniform<u32>,
+}
+
+impl PhonologyGen {
+    pub fn new(phonology: &Phonology) -> Result<Self, Error> {
+        let (min, max) = (phonology.min_syllables, phonology.max_syllables);
+        if min == 0 || min > max {
+            return Err(Error::Config(format!(
+                "Phonology `{}` needs a `min_syllables` of at least 1 and at most its `max_syllables` of {}, not {}",
+                phonology.name, max, min
+            )));
+        }
+        if let Some(f) = phonology.forbidden.iter().find(|f| Forbidden::new(f).text.is_empty()) {
+            return Err(Error::Config(format!(
+                "Phonology `{}` can't forbid the empty sequence `{}`",
+                phonology.name, f
+            )));
+        }
+        Ok(Self {
+            name: phonology.name.clone(),
+            onsets: Inventory::new(&phonology.name, "onsets", &phonology.onsets)?,
+            nuclei: Inventory::new(&phonology.name, "nuclei", &phonology.nuclei)?,
+            codas: Inventory::new(&phonology.name, "codas", &phonology.codas)?,
+            forbidden: phonology.forbidden.iter().map(|f| Forbidden::new(f)).collect(),
+            syllables: Uniform::from(min..=max),
+        })
+    }
+
+    /// Whether `word` contains no forbidden sequence.
+    fn allowed(&self, word: &str, finished: bool) -> bool {
+        !self.forbidden.iter().any(|f| f.broken_by(word, finished))
+    }
+
+    /// Append a sound from `inventory` to `word` that keeps it free of
+    /// forbidden sequences, or return false if there's none.
+    fn push_sound(
+        &self,
+        inventory: &Inventory,
+        word: &mut String,
+        finished: bool,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let len = word.len();
+        let i = inventory.table.sample(rng);
+        *word += &inventory.sounds[i];
+        if self.allowed(word, finished) {
+            return true;
+        }
+        // Pick again from only the sounds that fit.
+        let weights: Vec<f64> = inventory
+            .sounds
+            .iter()
+            .zip(&inventory.weights)
+            .map(|(s, w)| {
+                word.truncate(len);
+                *word += s;
+                if self.allowed(word, finished) {
+                    *w
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        word.truncate(len);
+        match AliasTable::new(&weights) {
+            Some(table) => {
+                *word += &inventory.sounds[table.sample(rng)];
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Build a word, or return `None` if it ran into a dead end.
+    fn build(&self, rng: &mut dyn RngCore) -> Option<String> {
+        let mut word = String::new();
+        let count = self.syllables.sample(rng);
+        for n in 1..=count {
+            let last = n == count;
+            let fits = self.push_sound(&self.onsets, &mut word, false, rng)
+                && self.push_sound(&self.nuclei, &mut word, false, rng)
+                && self.push_sound(&self.codas, &mut word, last, rng);
+            if !fits {
+                return None;
+            }
+        }
+        Some(word)
+    }
+}
+
+impl Generator for PhonologyGen {
+    fn generate_record(&self, rng: &mut dyn RngCore) -> Result<Record, Error> {
+        match self.build(rng) {
+            Some(word) => Ok(Record::from(word)),
+            None => Err(Error::Miss(Box::new(Error::Forbidden {
+                phonology: self.name.clone(),
+            }))),
+        }
+    }
+
+    fn pattern(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The phonology backend, for the configured phonology.
+pub(crate) fn phonology_generator(config: &Config) -> Result<Box<dyn Generator>, Error> {
+    match config.phonology()? {
+        Some(phonology) => Ok(Box::new(PhonologyGen::new(&phonology)?)),
+        None => Err(Error::Config(String::from(
+            "The `phonology` backend needs a phonology; pass one with `--phonology`",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn phonology_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut phonology = Phonology::builtins().remove(0);
+        phonology.forbidden.push(String::from("^r"));
+        phonology.forbidden.push(String::from("n$"));
+        let gen = PhonologyGen::new(&phonology).unwrap();
+        for _ in 0..200 {
+            let word = gen.generate(&mut rng).unwrap();
+            assert!(word.len() >= 2, "{}", word);
+            assert!(!word.starts_with('r') && !word.ends_with('n'), "{}", word);
+            for f in ["aa", "ii", "hh", "thth"] {
+                assert!(!word.contains(f), "{}", word);
+            }
+        }
+
+        // Every coda runs into a forbidden sequence with every onset.
+        phonology.onsets = vec![(String::from("t"), 1.0)].into_iter().collect();
+        phonology.codas = vec![(String::from("s"), 1.0)].into_iter().collect();
+        phonology.forbidden = vec![String::from("st")];
+        phonology.min_syllables = 2;
+        let gen = PhonologyGen::new(&phonology).unwrap();
+        match gen.generate(&mut rng) {
+            Err(Error::Forbidden { .. }) => {}
+            r => panic!("expected a forbidden sequence error, got {:?}", r),
+        }
+        phonology.min_syllables = 0;
+        assert!(PhonologyGen::new(&phonology).is_err());
+    }
+}
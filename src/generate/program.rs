@@ -0,0 +1,236 @@
+//! A compiled pattern laid out flat for fast generation.
+//!
+//! Instructions refer to their children by index, and everything sampling
+//! needs — class lookup tables, alias tables and length bounds — is worked
+//! out once when the program is built.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use regex_syntax::hir;
+
+use super::alias::AliasTable;
+use super::dfa::{interval_len, nth_in_interval};
+use super::node::{Assertion, Node};
+
+/// Index of an instruction in a `Program`.
+pub type InstId = usize;
+
+/// Looks up the `n`th character of a class in logarithmic time.
+#[derive(Debug, Clone)]
+pub struct ClassTable {
+    starts: Vec<u32>,
+    /// Number of characters in the ranges before each range.
+    offsets: Vec<u32>,
+    len: u32,
+}
+
+impl ClassTable {
+    fn new(cls: &hir::ClassUnicode) -> Self {
+        let mut starts = Vec::with_capacity(cls.ranges().len());
+        let mut offsets = Vec::with_capacity(cls.ranges().len());
+        let mut len = 0;
+        for r in cls.iter() {
+            starts.push(r.start() as u32);
+            offsets.push(len);
+            len += interval_len(r.start() as u32, r.end() as u32);
+        }
+        Self {
+            starts,
+            offsets,
+            len,
+        }
+    }
+
+    /// Number of characters in the class.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// The `n`th character of the class, for `n < len()`.
+    pub fn nth(&self, n: u32) -> char {
+        let i = self.offsets.partition_point(|o| *o <= n) - 1;
+        nth_in_interval(self.starts[i], n - self.offsets[i])
+    }
+}
+
+/// The branches of an alternation and how to pick one.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub weights: Vec<f64>,
+    pub table: AliasTable,
+}
+
+#[derive(Debug, Clone)]
+pub enum Inst {
+    Empty,
+    Literal(char),
+    /// Index into `Program::classes`.
+    Class(usize),
+    /// Range of `Program::children`.
+    Concat(Range<usize>),
+    Alternation {
+        /// Range of `Program::children`.
+        branches: Range<usize>,
+        /// Index into `Program::choices`.
+        choice: usize,
+    },
+    Repetition {
+        child: InstId,
+        min: u32,
+        max: Option<u32>,
+        /// Chance of a `?` repetition being taken, if not even.
+        probability: Option<f64>,
+    },
+    Capture {
+        child: InstId,
+        /// Where the group's text gets recorded.
+        slot: usize,
+    },
+    /// Repeat the text recorded in a slot.
+    BackRef(usize),
+    Assertion(Assertion),
+    /// Expand the grammar rule with this index.
+    Rule(usize),
+    /// Generate a string from the source with this index.
+    Source(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub insts: Vec<Inst>,
+    /// Fewest and most characters each instruction generates, with
+    /// `usize::MAX` for unbounded.
+    pub lens: Vec<(usize, usize)>,
+    pub children: Vec<InstId>,
+    /// For each entry of `children` in a concatenation, the fewest and most
+    /// characters the children after it generate between them.
+    pub rest: Vec<(usize, usize)>,
+    pub classes: Vec<ClassTable>,
+    pub choices: Vec<Choice>,
+    /// Number of capture groups whose text gets recorded.
+    pub slots: usize,
+    /// Names of the named groups, in the order they appear, and their slots.
+    pub fields: Vec<(String, usize)>,
+    /// Slot of each recorded group, by capture index.
+    slot_of: HashMap<u32, usize>,
+    pub root: InstId,
+}
+
+impl Program {
+    pub fn new(node: &Node) -> Self {
+        let mut program = Self {
+            insts: Vec::new(),
+            lens: Vec::new(),
+            children: Vec::new(),
+            rest: Vec::new(),
+            classes: Vec::new(),
+            choices: Vec::new(),
+            slots: 0,
+            fields: Vec::new(),
+            slot_of: HashMap::new(),
+            root: 0,
+        };
+        program.root = program.compile(node);
+        program
+    }
+
+    fn push(&mut self, inst: Inst, node: &Node) -> InstId {
+        self.insts.push(inst);
+        self.lens
+            .push((node.min_len(), node.max_len().unwrap_or(usize::MAX)));
+        self.insts.len() - 1
+    }
+
+    /// Compile `nodes` and store their ids contiguously in `children`.
+    fn compile_children(&mut self, nodes: &[Node]) -> Range<usize> {
+        let ids: Vec<InstId> = nodes.iter().map(|n| self.compile(n)).collect();
+        let start = self.children.len();
+        let mut rest = vec![(0usize, 0usize); ids.len()];
+        for i in (1..ids.len()).rev() {
+            let (min, max) = self.lens[ids[i]];
+            rest[i - 1] = (rest[i].0 + min, rest[i].1.saturating_add(max));
+        }
+        self.children.extend(ids);
+        self.rest.extend(rest);
+        start..self.children.len()
+    }
+
+    fn compile(&mut self, node: &Node) -> InstId {
+        let inst = match node {
+            Node::Empty => Inst::Empty,
+            Node::Literal(c) => Inst::Literal(*c),
+            Node::Class(cls) => {
+                self.classes.push(ClassTable::new(cls));
+                Inst::Class(self.classes.len() - 1)
+            }
+            Node::Concat(nodes) => Inst::Concat(self.compile_children(nodes)),
+            Node::Alternation {
+                branches,
+                weights,
+                table,
+            } => {
+                let branches = self.compile_children(branches);
+                self.choices.push(Choice {
+                    weights: weights.clone(),
+                    table: table.clone(),
+                });
+                Inst::Alternation {
+                    branches,
+                    choice: self.choices.len() - 1,
+                }
+            }
+            Node::Repetition {
+                node: child,
+                min,
+                max,
+                probability,
+            } => Inst::Repetition {
+                child: self.compile(child),
+                min: *min,
+                max: *max,
+                probability: *probability,
+            },
+            Node::Group(child) => return self.compile(child),
+            Node::Capture {
+                index,
+                name,
+                node: child,
+            } => {
+                let slot = self.slots;
+                self.slots += 1;
+                self.slot_of.insert(*index, slot);
+                if let Some(name) = name {
+                    self.fields.push((name.clone(), slot));
+                }
+                Inst::Capture {
+                    child: self.compile(child),
+                    slot,
+                }
+            }
+            // Back-references always come after their group.
+            Node::BackRef { index, .. } => Inst::BackRef(self.slot_of[index]),
+            Node::Assertion(a) => Inst::Assertion(a.clone()),
+            Node::Rule(rule) => Inst::Rule(*rule),
+            Node::Source(source) => Inst::Source(*source),
+        };
+        self.push(inst, node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_table_test() {
+        let cls = hir::ClassUnicode::new(vec![
+            hir::ClassUnicodeRange::new('a', 'c'),
+            hir::ClassUnicodeRange::new('\u{d7ff}', '\u{e000}'),
+            hir::ClassUnicodeRange::new('x', 'x'),
+        ]);
+        let table = ClassTable::new(&cls);
+        let chars: Vec<char> = (0..table.len()).map(|n| table.nth(n)).collect();
+        assert_eq!(chars, vec!['a', 'b', 'c', 'x', '\u{d7ff}', '\u{e000}']);
+    }
+}
@@ -1,209 +1,767 @@
 use rand::distributions::{Distribution, Uniform};
 use rand::prelude::*;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
 use regex_syntax::hir::{self, Hir, HirKind};
 use regex_syntax::Parser;
-use std::iter::FromIterator;
 
-use encoding::all::UTF_8;
-use encoding::{DecoderTrap, EncoderTrap, Encoding};
+use super::alias::AliasTable;
+use super::backend::{retry, Generator};
+use super::dfa::{Dfa, Limits, MAX_STATES};
+use super::enumerate::Enumerate;
+use super::extension;
+use super::stats::{self, LanguageSize, Stats};
+use super::uniform::UniformSampler;
+use super::node::{self, Assertion, Node};
+use super::program::{Choice, ClassTable, Inst, InstId, Program};
+use crate::config::{Frequencies, LengthUnit, RepeatDist, Sampling, WordList};
+use crate::error::Error;
 
-const MAX_REPEAT: u32 = 100;
+/// How many characters to try from a class for one that satisfies the
+/// assertions just before it.
+const CLASS_ATTEMPTS: u32 = 32;
+/// How long `stats` may take to count the strings of a pattern.
+const STATS_TIME: Duration = Duration::from_secs(5);
+/// Most automaton states and time to spend on the size an error reports,
+/// past which it's left unknown.
+const SIZE_STATES: usize = 2000;
+const SIZE_TIME: Duration = Duration::from_millis(500);
 
 struct RandomizeState<'a, R: Rng> {
     pub rng: &'a mut R,
+    pub program: &'a Program,
+    /// Extra repetitions of open-ended quantifiers.
+    pub repeat: &'a AliasTable,
+    /// Probability of each number of extra repetitions in `repeat`.
+    pub repeat_probs: &'a [f64],
+    /// The output generated so far.
+    pub out: String,
+    /// Length in characters of the output generated so far.
+    pub chars: usize,
+    pub assertions: Vec<(usize, &'a Assertion)>,
+    /// Byte range of the output each recorded group last generated.
+    pub captures: Vec<Option<Range<usize>>>,
+    /// What `#rule#` references expand to, if anything.
+    pub rules: Option<&'a dyn ExpandRule>,
+    pub sources: &'a [Source],
 }
 
-fn randomize_alternation<R: Rng>(
-    rstate: &mut RandomizeState<R>,
-    mut exprs: Vec<Hir>,
-) -> Result<String, ()> {
-    exprs.shuffle(rstate.rng);
-    if !exprs.is_empty() {
-        randomize_for(rstate, exprs[0].kind().clone())
-    } else {
-        Err(())
+impl<'a, R: Rng> RandomizeState<'a, R> {
+    fn emit(&mut self, c: char) {
+        self.out.push(c);
+        self.chars += 1;
+    }
+    /// Whether emitting `c` next keeps the assertions at the current position.
+    fn allows_next(&self, c: char) -> bool {
+        let pos = self.out.len();
+        let last = self.out.chars().next_back();
+        self.assertions
+            .iter()
+            .filter(|(p, _)| *p == pos)
+            .all(|(_, a)| a.holds(last, Some(c)))
     }
 }
 
-fn randomize_word_boundry<R: Rng>(
-    _rstate: &mut RandomizeState<R>,
-    _wb: hir::WordBoundary,
-) -> Result<String, ()> {
-    Ok(String::from(" "))
+/// Bounds on the number of characters a node may generate, so the whole
+/// output ends up within the configured length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Budget {
+    min: usize,
+    max: usize,
 }
 
-fn randomize_anchor<R: Rng>(
-    _rstate: &mut RandomizeState<R>,
-    _anchor: hir::Anchor,
-) -> Result<String, ()> {
-    Ok(String::from(""))
+impl Budget {
+    const UNBOUNDED: Budget = Budget {
+        min: 0,
+        max: usize::MAX,
+    };
+
+    /// Whether a node generating between `min` and `max` characters fits.
+    fn fits(&self, (min, max): (usize, usize)) -> bool {
+        min <= self.max && max >= self.min
+    }
+
+    /// The budget for a node followed by nodes generating `rest` characters,
+    /// after `used` characters have been generated.
+    fn before(&self, used: usize, rest: (usize, usize)) -> Budget {
+        Budget {
+            min: self.min.saturating_sub(used.saturating_add(rest.1)),
+            max: self.max.saturating_sub(used.saturating_add(rest.0)),
+        }
+    }
 }
 
-fn randomize_group<R: Rng>(
-    rstate: &mut RandomizeState<R>,
-    group: hir::Group,
-) -> Result<String, ()> {
-    randomize_for(rstate, group.hir.kind().clone())
+fn randomize_alternation<R: Rng>(
+    rstate: &mut RandomizeState<'_, R>,
+    branches: Range<usize>,
+    choice: &Choice,
+    budget: Budget,
+) -> Result<(), Error> {
+    let program = rstate.program;
+    let branches = &program.children[branches];
+    let mut i = choice.table.sample(rstate.rng);
+    if budget != Budget::UNBOUNDED && !budget.fits(program.lens[branches[i]]) {
+        // Pick again among the branches that fit, keeping their weights.
+        let fits: Vec<f64> = branches
+            .iter()
+            .zip(&choice.weights)
+            .map(|(b, w)| if budget.fits(program.lens[*b]) { *w } else { 0.0 })
+            .collect();
+        if let Some(table) = AliasTable::new(&fits) {
+            i = table.sample(rstate.rng);
+        }
+    }
+    randomize_for(rstate, branches[i], budget)
 }
 
-fn randomize_literal<R: Rng>(
-    _rstate: &mut RandomizeState<R>,
-    literal: hir::Literal,
-) -> Result<String, ()> {
-    match literal {
-        hir::Literal::Unicode(c) => Ok(String::from_iter([c].iter())),
-        hir::Literal::Byte(_) => Err(()),
+fn randomize_concat<R: Rng>(
+    rstate: &mut RandomizeState<'_, R>,
+    children: Range<usize>,
+    budget: Budget,
+) -> Result<(), Error> {
+    let program = rstate.program;
+    if budget == Budget::UNBOUNDED {
+        for id in &program.children[children] {
+            randomize_for(rstate, *id, budget)?;
+        }
+        return Ok(());
     }
+    let start = rstate.chars;
+    for (id, rest) in program.children[children.clone()]
+        .iter()
+        .zip(&program.rest[children])
+    {
+        let used = rstate.chars - start;
+        randomize_for(rstate, *id, budget.before(used, *rest))?;
+    }
+    Ok(())
 }
 
-fn randomize_concat<R: Rng>(rstate: &mut RandomizeState<R>, exprs: Vec<Hir>) -> Result<String, ()> {
-    let mut s = String::new();
-    for e in &exprs {
-        s += &randomize_for(rstate, e.kind().clone())?;
+fn repeat_exactly<R: Rng>(
+    rstate: &mut RandomizeState<'_, R>,
+    child: InstId,
+    n: u32,
+    budget: Budget,
+) -> Result<(), Error> {
+    if budget == Budget::UNBOUNDED {
+        for _ in 0..n {
+            randomize_for(rstate, child, budget)?;
+        }
+        return Ok(());
     }
-    Ok(s)
+    let (min, max) = rstate.program.lens[child];
+    let start = rstate.chars;
+    for i in 1..=n as usize {
+        let left = n as usize - i;
+        let rest = (min.saturating_mul(left), max.saturating_mul(left));
+        let used = rstate.chars - start;
+        randomize_for(rstate, child, budget.before(used, rest))?;
+    }
+    Ok(())
 }
 
-fn repeat_exactly<R: Rng>(rstate: &mut RandomizeState<R>, h: Hir, n: u32) -> Result<String, ()> {
-    let s = (0..n)
-        .map(|_| randomize_for(rstate, h.kind().clone()).unwrap())
-        .collect::<Vec<String>>()
-        .join("");
-    Ok(s)
+/// The repetition counts in `min..=max` that fit the budget, given that
+/// each repetition generates between `each.0` and `each.1` characters.
+fn counts_in_budget(min: u32, max: u32, each: (usize, usize), budget: Budget) -> (u32, u32) {
+    let (lo, hi) = (min as usize, max as usize);
+    let fewest = if each.1 == 0 {
+        lo
+    } else {
+        lo.max(budget.min.div_ceil(each.1))
+    };
+    let most = match budget.max.checked_div(each.0) {
+        Some(n) => hi.min(n),
+        None => hi,
+    };
+    (fewest.min(u32::MAX as usize) as u32, most as u32)
 }
 
-fn repeat_at_least<R: Rng + RngCore>(
-    rstate: &mut RandomizeState<R>,
-    h: Hir,
-    n: u32,
-) -> Result<String, ()> {
-    let dist = Uniform::from(n..MAX_REPEAT);
-    let n = dist.sample(rstate.rng);
-    let s = (0..n)
-        .map(|_| randomize_for(rstate, h.kind().clone()).unwrap())
-        .collect::<Vec<String>>()
-        .join("");
-    Ok(s)
-}
-
-fn repeat_bounded<R: Rng + RngCore>(
-    rstate: &mut RandomizeState<R>,
-    h: Hir,
-    mn: u32,
-    mx: u32,
-) -> Result<String, ()> {
-    let mx = mx + 1;
-    let dist = Uniform::from(mn..mx);
-    let n = dist.sample(rstate.rng);
-    let s = (0..n)
-        .map(|_| randomize_for(rstate, h.kind().clone()).unwrap())
-        .collect::<Vec<String>>()
-        .join("");
-    Ok(s)
-}
-
-fn randomize_unicode_class<R: Rng + RngCore>(
-    rstate: &mut RandomizeState<R>,
-    cls: hir::ClassUnicode,
-) -> Result<String, ()> {
-    let mut chars: Vec<char> = Vec::new();
-
-    for r in cls.iter() {
-        let s = r.start();
-        let e = r.end();
-        if let (Ok(s), Ok(e)) = (
-            UTF_8.encode(&String::from_iter([s].iter()), EncoderTrap::Strict),
-            UTF_8.encode(&String::from_iter([e].iter()), EncoderTrap::Strict),
-        ) {
-            if s.len() > 0 && e.len() > 0 {
-                let s = s[0];
-                let e = e[0] + 1;
-
-                for byte in s..e {
-                    if let Ok(s) = UTF_8.decode(&[byte], DecoderTrap::Strict) {
-                        let c = s.chars().nth(0).unwrap();
-                        chars.push(c);
+fn randomize_repetition<R: Rng>(
+    rstate: &mut RandomizeState<'_, R>,
+    child: InstId,
+    min: u32,
+    max: Option<u32>,
+    probability: Option<f64>,
+    budget: Budget,
+) -> Result<(), Error> {
+    let mut n = match (max, probability) {
+        (Some(1), Some(p)) => {
+            if rstate.rng.gen_bool(p) {
+                1
+            } else {
+                min
+            }
+        }
+        (Some(max), _) => Uniform::from(min..max.max(min) + 1).sample(rstate.rng),
+        (None, _) => min.saturating_add(rstate.repeat.sample(rstate.rng) as u32),
+    };
+    if budget != Budget::UNBOUNDED {
+        let each = rstate.program.lens[child];
+        let (fewest, most) = counts_in_budget(min, max.unwrap_or(u32::MAX), each, budget);
+        if fewest <= most && !(fewest..=most).contains(&n) {
+            n = match (max, probability) {
+                (Some(1), Some(_)) => fewest,
+                (Some(_), _) => rstate.rng.gen_range(fewest, most + 1),
+                (None, _) => {
+                    // Keep to the repetition distribution where it reaches,
+                    // and otherwise take the fewest repetitions that fit.
+                    let extra = |n: u32| (n - min) as usize;
+                    let probs = rstate.repeat_probs;
+                    let last = extra(most).min(probs.len().saturating_sub(1));
+                    let weights: Vec<f64> = (0..=last)
+                        .map(|k| if k >= extra(fewest) { probs[k] } else { 0.0 })
+                        .collect();
+                    match AliasTable::new(&weights) {
+                        Some(table) => min + table.sample(rstate.rng) as u32,
+                        None => fewest,
                     }
                 }
+            };
+        }
+    }
+    repeat_exactly(rstate, child, n, budget)
+}
+
+fn randomize_unicode_class<R: Rng>(rstate: &mut RandomizeState<'_, R>, cls: &ClassTable) {
+    // Pick a codepoint uniformly across the whole class, which weights each
+    // range by its size.
+    // Prefer a character that satisfies any word boundary right before it.
+    let mut c = cls.nth(rstate.rng.gen_range(0, cls.len()));
+    for _ in 0..CLASS_ATTEMPTS {
+        if rstate.allows_next(c) {
+            break;
+        }
+        c = cls.nth(rstate.rng.gen_range(0, cls.len()));
+    }
+    rstate.emit(c)
+}
+
+fn randomize_for<R: Rng>(
+    rstate: &mut RandomizeState<'_, R>,
+    id: InstId,
+    budget: Budget,
+) -> Result<(), Error> {
+    let program = rstate.program;
+    match &program.insts[id] {
+        Inst::Empty => {}
+        Inst::Literal(c) => rstate.emit(*c),
+        Inst::Class(cls) => randomize_unicode_class(rstate, &program.classes[*cls]),
+        Inst::Concat(children) => randomize_concat(rstate, children.clone(), budget)?,
+        Inst::Alternation { branches, choice } => {
+            randomize_alternation(rstate, branches.clone(), &program.choices[*choice], budget)?
+        }
+        Inst::Repetition {
+            child,
+            min,
+            max,
+            probability,
+        } => randomize_repetition(rstate, *child, *min, *max, *probability, budget)?,
+        Inst::Capture { child, slot } => {
+            let start = rstate.out.len();
+            randomize_for(rstate, *child, budget)?;
+            rstate.captures[*slot] = Some(start..rstate.out.len());
+        }
+        Inst::BackRef(slot) => {
+            if let Some(range) = rstate.captures[*slot].clone() {
+                // Copy in place, since `String::extend_from_within` is newer
+                // than the crate's minimum Rust version.
+                let mut i = range.start;
+                while i < range.end {
+                    let c = rstate.out[i..].chars().next().unwrap();
+                    rstate.out.push(c);
+                    rstate.chars += 1;
+                    i += c.len_utf8();
+                }
+            }
+        }
+        Inst::Assertion(a) => {
+            let pos = rstate.out.len();
+            rstate.assertions.push((pos, a));
+        }
+        Inst::Source(source) => {
+            // A miss there is a miss of the whole string.
+            let text = rstate.sources[*source].gen.generate_record(&mut *rstate.rng)?.text;
+            rstate.chars += text.chars().count();
+            rstate.out += &text;
+        }
+        Inst::Rule(rule) => {
+            if let Some(rules) = rstate.rules {
+                let start = rstate.out.len();
+                rules.expand_rule(*rule, &mut *rstate.rng, &mut rstate.out)?;
+                rstate.chars += rstate.out[start..].chars().count();
             }
         }
     }
+    Ok(())
+}
+
+/// Expands the `#rule#` references in a grammar's patterns.
+pub trait ExpandRule {
+    /// Append an expansion of the rule with index `rule` to `out`.
+    fn expand_rule(&self, rule: usize, rng: &mut dyn RngCore, out: &mut String)
+        -> Result<(), Error>;
+}
 
-    Ok(String::from_iter(&[*chars.choose(rstate.rng).unwrap()]))
+/// Another backend that `{&name}` in a pattern takes strings from, as if it
+/// were a word list.
+#[derive(Clone)]
+pub struct Source {
+    pub name: String,
+    pub gen: Arc<dyn Generator>,
 }
 
-fn randomize_class<R: Rng + RngCore>(
-    rstate: &mut RandomizeState<R>,
-    cls: hir::Class,
-) -> Result<String, ()> {
-    match cls {
-        hir::Class::Unicode(cls) => randomize_unicode_class(rstate, cls),
-        _ => Err(()),
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source")
+            .field("name", &self.name)
+            .field("pattern", &self.gen.pattern())
+            .finish()
     }
 }
 
-fn randomize_repetition<R: Rng>(
-    rstate: &mut RandomizeState<R>,
-    rep: hir::Repetition,
-) -> Result<String, ()> {
-    let hir = rep.hir;
-
-    match rep.kind {
-        hir::RepetitionKind::ZeroOrOne => repeat_bounded(rstate, hir.as_ref().clone(), 0, 1),
-        hir::RepetitionKind::ZeroOrMore => {
-            repeat_bounded(rstate, hir.as_ref().clone(), 0, MAX_REPEAT)
-        }
-        hir::RepetitionKind::OneOrMore => {
-            repeat_bounded(rstate, hir.as_ref().clone(), 1, MAX_REPEAT)
-        }
-        hir::RepetitionKind::Range(range) => match range {
-            hir::RepetitionRange::Exactly(n) => repeat_exactly(rstate, hir.as_ref().clone(), n),
-            hir::RepetitionRange::AtLeast(n) => repeat_at_least(rstate, hir.as_ref().clone(), n),
-            hir::RepetitionRange::Bounded(mn, mx) => {
-                repeat_bounded(rstate, hir.as_ref().clone(), mn, mx)
-            }
-        },
+/// Settings for how a `RegexGen` samples its pattern.
+#[derive(Debug, Clone, Default)]
+pub struct RegexGenOptions {
+    pub repeat: RepeatDist,
+    pub sampling: Sampling,
+    /// Shortest string to generate, in characters.
+    pub min_len: Option<usize>,
+    /// Longest string to generate, in characters. Required for
+    /// `Sampling::Uniform` if the pattern can generate strings of any length.
+    pub max_len: Option<usize>,
+    /// Weights for the characters of classes and single-character
+    /// alternations.
+    pub frequencies: Option<Frequencies>,
+    /// Characters to limit classes to.
+    pub charset: Option<hir::ClassUnicode>,
+    /// Word lists that `{&name}` in the pattern can refer to.
+    pub word_lists: Vec<WordList>,
+    /// Other backends that `{&name}` in the pattern can refer to.
+    pub sources: Vec<Source>,
+    /// Grammar rules that `#name#` in the pattern can refer to. Without
+    /// any, `#` is a literal.
+    pub rules: Vec<String>,
+}
+
+/// A generated string, along with the text each named group in the pattern
+/// generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub text: String,
+    /// Each named group, in the order they appear in the pattern, with `None`
+    /// for groups that weren't part of the string.
+    pub fields: Vec<(String, Option<String>)>,
+    /// How pronounceable the string is, from 0 to 1, if it was rated.
+    pub score: Option<f64>,
+}
+
+impl Record {
+    /// The text of the named group `name`, if it was part of the string.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, text)| text.as_deref())
+    }
+}
+
+impl From<String> for Record {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            fields: Vec::new(),
+            score: None,
+        }
     }
 }
 
-fn randomize_for<R: Rng>(rstate: &mut RandomizeState<R>, kind: HirKind) -> Result<String, ()> {
-    match kind {
-        HirKind::Alternation(exprs) => randomize_alternation(rstate, exprs),
-        HirKind::Literal(lit) => randomize_literal(rstate, lit),
-        HirKind::Concat(exprs) => randomize_concat(rstate, exprs),
-        HirKind::Repetition(rep) => randomize_repetition(rstate, rep),
-        HirKind::Group(grp) => randomize_group(rstate, grp),
-        HirKind::Class(cls) => randomize_class(rstate, cls),
-        HirKind::Anchor(a) => randomize_anchor(rstate, a),
-        HirKind::WordBoundary(wb) => randomize_word_boundry(rstate, wb),
-        _ => Err(()),
+/// An endless iterator of strings from a `RegexGen`; see `RegexGen::iter`.
+pub struct Iter<'a, R: Rng> {
+    gen: &'a RegexGen,
+    rng: R,
+}
+
+impl<R: Rng> Iterator for Iter<'_, R> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.gen.randomize(&mut self.rng))
     }
 }
 
 pub struct RegexGen {
     hir: Hir,
+    pattern: String,
+    /// `pattern` with its extensions rewritten into markers.
+    extended: String,
+    /// The pattern's tree, for analysis.
+    root: Node,
+    /// The pattern compiled for generation.
+    program: Program,
+    repeat: AliasTable,
+    /// Probability of each number of extra repetitions in `repeat`.
+    repeat_probs: Vec<f64>,
+    /// Set when sampling uniformly, along with a regex to check the
+    /// pattern's anchors and word boundaries and find its named groups, if it
+    /// has any.
+    uniform: Option<(UniformSampler, Option<regex::Regex>)>,
+    /// Length bounds of the whole output.
+    budget: Budget,
+    /// Other backends the pattern takes strings from.
+    sources: Vec<Source>,
 }
 
 impl RegexGen {
-    pub fn new(pattern: &str) -> Option<Self> {
-        if let Ok(hir) = Parser::new().parse(pattern) {
-            Some(Self { hir })
-        } else {
-            None
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        Self::with_options(pattern, &Default::default())
+    }
+    pub fn with_options(pattern: &str, options: &RegexGenOptions) -> Result<Self, Error> {
+        options.repeat.validate().map_err(Error::Config)?;
+        let weights = options.repeat.weights();
+        let repeat = match AliasTable::new(&weights) {
+            Some(table) => table,
+            None => {
+                return Err(Error::Config(format!(
+                    "The repetition distribution `{}` has no weight below its cap",
+                    options.repeat
+                )))
+            }
+        };
+        let ext = extension::preprocess(pattern, &options.rules)?;
+        let hir = match Parser::new().parse(&ext.pattern) {
+            Ok(hir) => hir,
+            Err(source) => {
+                return Err(Error::parse(pattern, source, |span| {
+                    ext.original_span(span)
+                }))
+            }
+        };
+        let mut root = node::compile(
+            &hir,
+            pattern,
+            &ext.annotations,
+            &options.word_lists,
+            &options.sources,
+        )?;
+        if let Some(charset) = &options.charset {
+            root.restrict_classes(charset, pattern)?;
+        }
+        if let Some(freq) = &options.frequencies {
+            root.apply_frequencies(freq);
+        }
+        let total: f64 = weights.iter().sum();
+        let mut gen = Self {
+            hir,
+            pattern: String::from(pattern),
+            extended: ext.pattern,
+            program: Program::new(&root),
+            root,
+            repeat,
+            repeat_probs: weights.iter().map(|w| w / total).collect(),
+            uniform: None,
+            sources: options.sources.clone(),
+            budget: Budget {
+                min: options.min_len.unwrap_or(0),
+                max: options.max_len.unwrap_or(usize::MAX),
+            },
+        };
+        if !gen.budget.fits(gen.program.lens[gen.program.root]) {
+            return Err(gen.length_error());
         }
+        if options.sampling == Sampling::Uniform {
+            if gen.root.has_backrefs() {
+                return Err(Error::Unsupported {
+                    pattern: String::from(pattern),
+                    kind: "a back-reference with uniform sampling",
+                });
+            }
+            gen.require_self_contained("another backend's strings with uniform sampling")?;
+            let max_len = gen.length_bound(options.max_len)?;
+            let dfa = Dfa::new(&gen.root, pattern)?;
+            let sampler = UniformSampler::new(dfa, gen.budget.min, max_len);
+            if sampler.is_empty() {
+                return Err(gen.length_error());
+            }
+            let matcher = if gen.program.fields.is_empty() {
+                gen.verifier()?
+            } else {
+                Some(gen.full_match()?)
+            };
+            gen.uniform = Some((sampler, matcher));
+        }
+        Ok(gen)
+    }
+    fn length_error(&self) -> Error {
+        let bound = |n: usize, unset: usize| if n == unset { None } else { Some(n) };
+        Error::Length {
+            pattern: self.pattern.clone(),
+            min_len: bound(self.budget.min, 0),
+            max_len: bound(self.budget.max, usize::MAX),
+            unit: LengthUnit::Chars,
+        }
+    }
+
+    /// The pattern strings are generated from.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
     }
     pub fn kind(&self) -> &HirKind {
         self.hir.kind()
     }
-    pub fn randomize(&self, rng: &mut impl Rng) -> Result<String, ()> {
-        let mut rstate = RandomizeState { rng: rng };
-        randomize_for(&mut rstate, self.kind().clone())
+    pub fn randomize(&self, rng: &mut impl Rng) -> Result<String, Error> {
+        self.randomize_record(rng).map(|r| r.text)
+    }
+
+    /// Names of the pattern's named groups, in the order they appear.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.program.fields.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// An error saying `kind` isn't supported if the pattern has rule
+    /// references or sources, whose strings it can't analyze.
+    fn require_self_contained(&self, kind: &'static str) -> Result<(), Error> {
+        if self
+            .program
+            .insts
+            .iter()
+            .any(|inst| matches!(inst, Inst::Rule(_) | Inst::Source(_)))
+        {
+            return Err(Error::Unsupported {
+                pattern: self.pattern.clone(),
+                kind,
+            });
+        }
+        Ok(())
+    }
+
+    /// The indices of the rules the pattern refers to.
+    pub fn rule_refs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.program.insts.iter().filter_map(|inst| match inst {
+            Inst::Rule(rule) => Some(*rule),
+            _ => None,
+        })
+    }
+
+    /// Generate a string along with the text of each named group.
+    pub fn randomize_record(&self, rng: &mut impl Rng) -> Result<Record, Error> {
+        retry(|| self.draw(rng, None))
+    }
+
+    /// Generate a string, expanding its `#rule#` references with `rules`,
+    /// or `Error::Miss` if it has to be drawn again.
+    pub fn randomize_with_rules(
+        &self,
+        rng: &mut impl Rng,
+        rules: &dyn ExpandRule,
+    ) -> Result<Record, Error> {
+        self.draw(rng, Some(rules))
+    }
+
+    /// Draw one string, or `Error::Miss` if it has to be thrown away.
+    fn draw(&self, rng: &mut impl Rng, rules: Option<&dyn ExpandRule>) -> Result<Record, Error> {
+        if let Some((sampler, matcher)) = &self.uniform {
+            // Throwing away strings that break an assertion keeps the rest
+            // equally likely.
+            let text = sampler.sample(rng);
+            let re = match matcher {
+                Some(re) => re,
+                None => return Ok(Record::from(text)),
+            };
+            let caps = match re.captures(&text) {
+                Some(caps) => caps,
+                None => return Err(self.unsatisfiable()),
+            };
+            let fields = self
+                .program
+                .fields
+                .iter()
+                .map(|(name, _)| (name.clone(), caps.name(name).map(|m| String::from(m.as_str()))))
+                .collect();
+            return Ok(Record {
+                text,
+                fields,
+                score: None,
+            });
+        }
+        let mut rstate = RandomizeState {
+            rng,
+            program: &self.program,
+            repeat: &self.repeat,
+            repeat_probs: &self.repeat_probs,
+            out: String::new(),
+            chars: 0,
+            assertions: Vec::new(),
+            captures: vec![None; self.program.slots],
+            rules,
+            sources: &self.sources,
+        };
+        // Anchors and word boundaries depend on characters that haven't been
+        // generated yet when they are reached, so check them afterwards and
+        // miss if any of them don't hold. The length budget only steers
+        // generation by each node's shortest and longest output, which can
+        // miss, so it gets checked too.
+        randomize_for(&mut rstate, self.program.root, self.budget)?;
+        let s = &rstate.out;
+        if !self.budget.fits((rstate.chars, rstate.chars)) {
+            return Err(Error::Miss(Box::new(self.length_error())));
+        }
+        if !rstate
+            .assertions
+            .iter()
+            .all(|(pos, a)| a.holds(s[..*pos].chars().next_back(), s[*pos..].chars().next()))
+        {
+            return Err(self.unsatisfiable());
+        }
+        let fields = self
+            .program
+            .fields
+            .iter()
+            .map(|(name, slot)| {
+                let text = rstate.captures[*slot].clone().map(|r| String::from(&s[r]));
+                (name.clone(), text)
+            })
+            .collect();
+        Ok(Record {
+            text: rstate.out,
+            fields,
+            score: None,
+        })
+    }
+
+    /// A miss for a string whose anchors or word boundaries didn't hold.
+    fn unsatisfiable(&self) -> Error {
+        Error::Miss(Box::new(Error::Unsatisfiable {
+            pattern: self.pattern.clone(),
+        }))
+    }
+
+    /// Generate strings lazily and without end, drawing randomness from
+    /// `rng`, which can be a `&mut` to an existing generator.
+    pub fn iter<R: Rng>(&self, rng: R) -> Iter<'_, R> {
+        Iter { gen: self, rng }
+    }
+
+    /// Every string the pattern can generate, in shortlex order, skipping
+    /// those shorter than the `min_len` option.
+    ///
+    /// Patterns that can generate arbitrarily long strings need a `max_len`,
+    /// in characters.
+    pub fn enumerate(&self, max_len: Option<usize>) -> Result<Enumerate, Error> {
+        if self.root.has_backrefs() {
+            return Err(Error::Unsupported {
+                pattern: self.pattern.clone(),
+                kind: "a back-reference when listing every string",
+            });
+        }
+        self.require_self_contained("another backend's strings when listing every string")?;
+        let max_len = self.length_bound(max_len)?;
+        let min_len = self.root.min_len().max(self.budget.min);
+        let dfa = Dfa::new(&self.root, &self.pattern)?;
+        Ok(Enumerate::new(dfa, min_len, max_len, self.verifier()?))
+    }
+
+    /// The length of the longest string the pattern can generate, capped at
+    /// `max_len`, which is required if the pattern is unbounded.
+    fn length_bound(&self, max_len: Option<usize>) -> Result<usize, Error> {
+        match (self.root.max_len(), max_len) {
+            (Some(len), Some(max_len)) => Ok(len.min(max_len)),
+            (Some(len), None) | (None, Some(len)) => Ok(len),
+            (None, None) => Err(Error::Unbounded {
+                pattern: self.pattern.clone(),
+            }),
+        }
+    }
+
+    /// A regex for checking the pattern's anchors and word boundaries, which
+    /// its automaton ignores, if it has any.
+    fn verifier(&self) -> Result<Option<regex::Regex>, Error> {
+        if !self.root.has_assertions() {
+            return Ok(None);
+        }
+        self.full_match().map(Some)
+    }
+
+    /// A regex matching exactly the strings the pattern matches.
+    fn full_match(&self) -> Result<regex::Regex, Error> {
+        match regex::Regex::new(&format!(r"\A(?:{})\z", self.extended)) {
+            Ok(re) => Ok(re),
+            Err(_) => Err(Error::TooComplex {
+                pattern: self.pattern.clone(),
+            }),
+        }
+    }
+
+    /// The number of distinct strings the pattern can generate, up to
+    /// `max_len` characters if given, and how evenly they get sampled.
+    ///
+    /// Gives up on patterns whose automaton takes too long to count.
+    pub fn stats(&self, max_len: Option<usize>) -> Result<Stats, Error> {
+        self.stats_within(max_len, &Limits::timed(MAX_STATES, STATS_TIME))
+    }
+
+    /// `stats`, or an error if working them out goes past `limits`.
+    pub fn stats_within(&self, max_len: Option<usize>, limits: &Limits) -> Result<Stats, Error> {
+        let size = self.size_within(max_len, limits)?;
+        let (entropy, collision) = match (&self.uniform, size.count()) {
+            // Every string is as likely as any other.
+            (Some(_), Some(count)) => {
+                let entropy = stats::log2(count);
+                (entropy, (-entropy).exp2())
+            }
+            _ => stats::sampling_stats(&self.root, &self.repeat_probs),
+        };
+        Ok(Stats {
+            size,
+            entropy,
+            collision,
+        })
+    }
+
+    /// The number of distinct strings the pattern can generate, up to
+    /// `max_len` characters if given, or an error if counting them goes past
+    /// `limits`.
+    fn size_within(&self, max_len: Option<usize>, limits: &Limits) -> Result<LanguageSize, Error> {
+        self.require_self_contained("another backend's strings when counting strings")?;
+        let max_len = match (self.root.max_len(), max_len) {
+            (Some(len), Some(max_len)) => Some(len.min(max_len)),
+            (len, max_len) => len.or(max_len),
+        };
+        let max_len = match max_len {
+            Some(max_len) => max_len,
+            None => return Ok(LanguageSize::Unbounded),
+        };
+        let dfa = Dfa::with_limits(&self.root, &self.pattern, limits)?;
+        let count = dfa.count(max_len, limits, &self.pattern)?;
+        if self.root.has_assertions() || self.root.has_backrefs() {
+            Ok(LanguageSize::AtMost(count))
+        } else {
+            Ok(LanguageSize::Exact(count))
+        }
+    }
+}
+
+impl Generator for RegexGen {
+    fn generate_record(&self, rng: &mut dyn RngCore) -> Result<Record, Error> {
+        self.draw(&mut &mut *rng, None)
+    }
+
+    fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    fn size(&self, max_len: Option<usize>) -> Option<LanguageSize> {
+        // Only a small automaton is worth building just for an error message.
+        self.size_within(max_len, &Limits::timed(SIZE_STATES, SIZE_TIME))
+            .ok()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Charset;
     use rand_xoshiro::Xoshiro256StarStar;
     #[test]
     fn hir_randomize_test() {
@@ -213,6 +771,308 @@ mod tests {
             println!("{}", s);
         }
     }
+    fn sample_class(pattern: &str, n: usize) -> Vec<char> {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let gen = RegexGen::new(pattern).unwrap();
+        (0..n)
+            .map(|_| {
+                let s = gen.randomize(&mut rng).unwrap();
+                assert_eq!(s.chars().count(), 1);
+                s.chars().next().unwrap()
+            })
+            .collect()
+    }
+    #[test]
+    fn unicode_class_multibyte_test() {
+        let chars = sample_class("[\u{e0}-\u{ff}]", 2000);
+        assert!(chars.iter().all(|c| ('\u{e0}'..='\u{ff}').contains(c)));
+        for c in '\u{e0}'..='\u{ff}' {
+            assert!(chars.contains(&c), "{:?} never sampled", c);
+        }
+
+        let chars = sample_class("[\u{3041}-\u{3096}]", 5000);
+        assert!(chars.iter().all(|c| ('\u{3041}'..='\u{3096}').contains(c)));
+        for c in '\u{3041}'..='\u{3096}' {
+            assert!(chars.contains(&c), "{:?} never sampled", c);
+        }
+
+        let greek = regex::Regex::new("^\\p{Greek}$").unwrap();
+        for c in sample_class("\\p{Greek}", 500) {
+            assert!(greek.is_match(&c.to_string()), "{:?} is not Greek", c);
+        }
+    }
+    #[test]
+    fn unicode_class_weighted_by_range_size_test() {
+        // `a` is one codepoint out of 27, so it should be rare.
+        let chars = sample_class("[a\u{3b1}-\u{3ca}]", 2700);
+        let a_count = chars.iter().filter(|c| **c == 'a').count();
+        assert!(a_count > 30 && a_count < 200, "`a` sampled {} times", a_count);
+    }
+    #[test]
+    fn unicode_class_surrogate_gap_test() {
+        let chars = sample_class("[\u{d7ff}-\u{e000}]", 200);
+        assert!(chars.contains(&'\u{d7ff}'));
+        assert!(chars.contains(&'\u{e000}'));
+    }
+    #[test]
+    fn unsupported_error_test() {
+        match RegexGen::new("(?-u)[a-z]") {
+            Err(Error::Unsupported { kind, .. }) => assert_eq!(kind, "a byte class"),
+            Err(e) => panic!("expected unsupported error, got {:?}", e),
+            Ok(_) => panic!("expected unsupported error"),
+        }
+    }
+    #[test]
+    fn parse_error_span_test() {
+        match RegexGen::new("ab(cd") {
+            Err(e) => assert_eq!(e.location(), Some(("ab(cd", 2..3))),
+            Ok(_) => panic!("expected parse error"),
+        }
+    }
+    fn assert_generates_matches(pattern: &str) {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let gen = RegexGen::new(pattern).unwrap();
+        let re = regex::Regex::new(&format!("\\A(?:{})\\z", pattern)).unwrap();
+        for _ in 0..100 {
+            let s = gen.randomize(&mut rng).unwrap();
+            assert!(re.is_match(&s), "{:?} does not match {:?}", s, pattern);
+        }
+    }
+    #[test]
+    fn empty_test() {
+        assert_generates_matches("(a|)");
+        assert_generates_matches("x?|");
+        assert_generates_matches("()b");
+    }
+    #[test]
+    fn anchor_test() {
+        assert_generates_matches("^abc$");
+        assert_generates_matches("\\A[a-z]{3}\\z");
+        assert_generates_matches("(?m)a(\n|b)^c");
+        assert_generates_matches("(?m)[ab]$[\nc]");
+    }
+    #[test]
+    fn word_boundary_test() {
+        assert_generates_matches("\\bfoo\\b");
+        assert_generates_matches("x[a-z ]\\bfoo\\b[ a-z]y");
+        assert_generates_matches("foo\\B[a-z ]");
+        assert_generates_matches("(?-u:\\b)[a-z]+(?-u:\\b)");
+    }
+    fn count_outputs(pattern: &str, n: usize) -> std::collections::HashMap<String, usize> {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let gen = RegexGen::new(pattern).unwrap();
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..n {
+            *counts.entry(gen.randomize(&mut rng).unwrap()).or_insert(0) += 1;
+        }
+        counts
+    }
+    #[test]
+    fn weighted_alternation_test() {
+        let counts = count_outputs("{=8}a|b|{=1}c|{=0}d", 10000);
+        assert!(counts["a"] > 7600 && counts["a"] < 8400, "{:?}", counts);
+        assert!(counts["b"] > 800 && counts["b"] < 1200, "{:?}", counts);
+        assert!(!counts.contains_key("d"));
+
+        let counts = count_outputs("x(?:{=3}|y)", 4000);
+        assert!(counts["x"] > 2800 && counts["x"] < 3200, "{:?}", counts);
+    }
+    #[test]
+    fn optional_probability_test() {
+        let counts = count_outputs("ka'{?0.1}ri", 10000);
+        assert!(counts["ka'ri"] > 800 && counts["ka'ri"] < 1200, "{:?}", counts);
+        assert_eq!(counts["ka'ri"] + counts["kari"], 10000);
+
+        let counts = count_outputs("(?:ab){?1}c", 100);
+        assert_eq!(counts["abc"], 100);
+    }
+    #[test]
+    fn length_budget_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(6);
+        for (pattern, min_len, max_len) in &[
+            ("[a-z]{1,20}", 5, 6),
+            ("(a|bbbbbbbb)+c?", 3, 4),
+            ("(ab|c){?0.9}d{2,9}(x|yyy)", 4, 5),
+            ("x*", 20, 20),
+        ] {
+            let options = RegexGenOptions {
+                repeat: RepeatDist::Uniform { cap: 2 },
+                min_len: Some(*min_len),
+                max_len: Some(*max_len),
+                ..Default::default()
+            };
+            let gen = RegexGen::with_options(pattern, &options).unwrap();
+            for _ in 0..200 {
+                let len = gen.randomize(&mut rng).unwrap().chars().count();
+                assert!((*min_len..=*max_len).contains(&len), "{}: {}", pattern, len);
+            }
+        }
+        let options = RegexGenOptions {
+            max_len: Some(2),
+            ..Default::default()
+        };
+        assert!(RegexGen::with_options("abc", &options).is_err());
+    }
+    #[test]
+    fn backref_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+        let gen = RegexGen::new("([a-z]{2,4})-\\1(?P<x>[0-9]|!)?\\k<x>").unwrap();
+        for _ in 0..100 {
+            let s = gen.randomize(&mut rng).unwrap();
+            let (left, right) = s.split_once('-').unwrap();
+            assert!(right.starts_with(left), "{:?}", s);
+            let rest = &right[left.len()..];
+            let half = rest.len() / 2;
+            assert_eq!(&rest[..half], &rest[half..], "{:?}", s);
+        }
+        let options = RegexGenOptions {
+            sampling: Sampling::Uniform,
+            ..Default::default()
+        };
+        assert!(matches!(
+            RegexGen::with_options("(a|b)\\1", &options),
+            Err(Error::Unsupported { .. })
+        ));
+        let gen = RegexGen::new("(a|bc)\\1").unwrap();
+        assert!(gen.enumerate(None).is_err());
+        let size = gen.stats(None).unwrap().size;
+        assert_eq!(size, LanguageSize::AtMost(num_bigint::BigUint::from(6u32)));
+    }
+    #[test]
+    fn frequencies_test() {
+        let english = crate::config::FrequencyTable::builtins()
+            .remove(0)
+            .resolve()
+            .unwrap();
+        let options = RegexGenOptions {
+            frequencies: Some(english),
+            ..Default::default()
+        };
+        let mut rng = Xoshiro256StarStar::seed_from_u64(8);
+        let gen = RegexGen::with_options("[a-zA-Z0-9]", &options).unwrap();
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..20000 {
+            *counts.entry(gen.randomize(&mut rng).unwrap()).or_insert(0) += 1;
+        }
+        // `e` is 12.7 of a total weight of 200 + 10 * 3.85 for the digits.
+        assert!(counts["e"] > 950 && counts["e"] < 1200, "{:?}", counts);
+        assert!(counts["E"] > 950 && counts["E"] < 1200, "{:?}", counts);
+        assert!(counts.get("z").copied().unwrap_or(0) < 30, "{:?}", counts);
+        assert!(counts["7"] > 260 && counts["7"] < 390, "{:?}", counts);
+
+        let gen = RegexGen::with_options("(e|z|q)", &options).unwrap();
+        let es = (0..1000)
+            .filter(|_| gen.randomize(&mut rng).unwrap() == "e")
+            .count();
+        assert!(es > 960, "{}", es);
+    }
+    #[test]
+    fn iter_test() {
+        let gen = RegexGen::new("[a-z]{3}").unwrap();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let strings: Vec<String> = gen.iter(&mut rng).take(5).map(Result::unwrap).collect();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        for s in &strings {
+            assert_eq!(*s, gen.randomize(&mut rng).unwrap());
+        }
+    }
+    #[test]
+    fn repeat_dist_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let options = RegexGenOptions {
+            repeat: RepeatDist::Poisson { mean: 3.0, cap: 5 },
+            ..Default::default()
+        };
+        let gen = RegexGen::with_options("x[a-z]+", &options).unwrap();
+        let mut total = 0;
+        for _ in 0..2000 {
+            let len = gen.randomize(&mut rng).unwrap().len();
+            assert!((2..=7).contains(&len), "length {}", len);
+            total += len;
+        }
+        let mean = total as f64 / 2000.0;
+        assert!(mean > 4.6 && mean < 5.2, "mean length {}", mean);
+
+        // Minimums past the cap of the distribution are fine.
+        let gen = RegexGen::with_options("a{150,}", &options).unwrap();
+        let len = gen.randomize(&mut rng).unwrap().len();
+        assert!((150..=155).contains(&len));
+
+        let options = RegexGenOptions {
+            repeat: RepeatDist::Normal {
+                mean: 1000.0,
+                std_dev: 1.0,
+                cap: 3,
+            },
+            ..Default::default()
+        };
+        assert!(RegexGen::with_options("a*", &options).is_err());
+    }
+    #[test]
+    fn extension_error_span_test() {
+        match RegexGen::new("{=2}a|b{?0.5}(") {
+            Err(e) => assert_eq!(e.location(), Some(("{=2}a|b{?0.5}(", 13..14))),
+            Ok(_) => panic!("expected parse error"),
+        }
+        match RegexGen::new("{=0}a|{=0}b") {
+            Err(Error::Unsupported { .. }) => {}
+            Err(e) => panic!("expected unsupported error, got {:?}", e),
+            Ok(_) => panic!("expected unsupported error"),
+        }
+    }
+    #[test]
+    fn unsatisfiable_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let gen = RegexGen::new("a\\bb").unwrap();
+        match gen.randomize(&mut rng) {
+            Err(Error::Unsatisfiable { .. }) => {}
+            r => panic!("expected unsatisfiable error, got {:?}", r),
+        }
+    }
+    #[test]
+    fn charset_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let charset = |name| {
+            let charset = Charset::builtins().into_iter().find(|c| c.name == name);
+            RegexGenOptions {
+                charset: Some(charset.unwrap().resolve().unwrap()),
+                ..Default::default()
+            }
+        };
+        let gen = RegexGen::with_options(".{20}", &charset("ascii-printable")).unwrap();
+        for _ in 0..50 {
+            let s = gen.randomize(&mut rng).unwrap();
+            assert!(s.chars().all(|c| (' '..='~').contains(&c)), "{:?}", s);
+        }
+        let gen = RegexGen::with_options("[0-9Ol]{3}", &charset("no-ambiguous")).unwrap();
+        for _ in 0..50 {
+            let s = gen.randomize(&mut rng).unwrap();
+            assert!(s.chars().all(|c| ('2'..='9').contains(&c)), "{:?}", s);
+        }
+        match RegexGen::with_options("a[0O]", &charset("no-ambiguous")) {
+            Err(Error::Unsupported { .. }) => {}
+            r => panic!("expected unsupported error, got {:?}", r.map(|g| g.pattern)),
+        }
+    }
+    #[test]
+    fn word_list_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let options = RegexGenOptions {
+            word_lists: vec![WordList::parse("animal", "wolf\nred fox\t3\nowl\t0\n").unwrap()],
+            ..Default::default()
+        };
+        let gen = RegexGen::with_options("{&animal}-{&animal}{?0.5}", &options).unwrap();
+        for _ in 0..100 {
+            let s = gen.randomize(&mut rng).unwrap();
+            let words: Vec<&str> = s.split('-').collect();
+            assert!(words.iter().all(|w| ["wolf", "red fox", ""].contains(w)), "{}", s);
+        }
+        assert_eq!(gen.stats(None).unwrap().size, LanguageSize::Exact(6u32.into()));
+        match RegexGen::with_options("{&plant}", &options) {
+            Err(Error::Config(_)) => {}
+            r => panic!("expected a config error, got {:?}", r.map(|g| g.pattern)),
+        }
+    }
     #[test]
     fn hir_parser_test() {
         let hir = Parser::new().parse("a|b").unwrap();
@@ -0,0 +1,141 @@
+//! Rating how pronounceable a string is.
+
+use super::diversity::is_vowel;
+use super::markov::MarkovModel;
+use crate::error::Error;
+
+/// Common English words the built-in model learns which letters follow
+/// which from.
+const ENGLISH_WORDS: &str = "about above across after again against almost alone along already \
+also always among animal another answer apple around away baby back baker balance banner basket \
+beautiful because become before began behind being below better between bird black blanket \
+blossom body border bottle branch bread bright brother brown butter button call came candle \
+captain carry castle center certain change children circle city clear close cloud color common \
+copper corner cotton country course cover dance danger daughter dinner distance doctor dollar \
+dragon during early earth either elephant enemy engine enough evening every family farmer father \
+feather field finger finish flower follow forest forward friend garden gather gentle giant \
+golden govern green ground grow hammer happen harbor heavy hello hidden history holiday honest \
+horse hunter island kitchen ladder lantern later leader lemon letter little london lonely \
+maiden market marble master matter meadow melody member middle minute mirror moment money \
+monster morning mother mountain music narrow nature never number ocean office orange order other \
+over paper parent pattern people pepper person picture pillow planet plenty pocket poem police \
+pony potato power present pretty problem promise purple question quiet rabbit ribbon river \
+robin rocket salad sailor second secret seven shadow shelter silver simple sister soldier \
+spider spirit spring station stone story summer sunset supper table teacher temple thunder \
+timber today tomato tower travel treasure under valley village violin visit water weather \
+window winter wisdom wonder yellow yesterday amelia benjamin caroline daniel edward eleanor \
+gabriel hannah isabel jasper julian katherine lucas madeline marian nathan oliver rosalind \
+samuel sophia tobias victor william";
+
+/// Longest run of consonants that still reads easily.
+const EASY_CONSONANT_RUN: usize = 2;
+
+/// Share of the training words that fit the model less well than a string
+/// needs to for full marks.
+const PLAUSIBLE_QUANTILE: f64 = 0.1;
+
+/// Rates strings from 0, unpronounceable, to 1, easy to say, by how well
+/// their letters follow a character model, how long their runs of
+/// consonants are and how balanced their vowels and consonants are.
+#[derive(Debug, Clone)]
+pub struct Scorer {
+    model: MarkovModel,
+    alphabet: usize,
+    /// Mean log likelihood of a letter picked uniformly at random.
+    random: f64,
+    /// Mean log likelihood per letter that gets full marks.
+    plausible: f64,
+}
+
+impl Scorer {
+    /// A scorer that learns from the words `model` was trained on, or from
+    /// common English words.
+    pub fn new(model: Option<&MarkovModel>) -> Result<Self, Error> {
+        let words: Vec<String> = match model {
+            Some(model) => model.words().map(str::to_lowercase).collect(),
+            None => ENGLISH_WORDS.split_whitespace().map(String::from).collect(),
+        };
+        let model = MarkovModel::train(words.iter().map(String::as_str), 2)?;
+        let alphabet = model.alphabet_size();
+        let mut fits: Vec<f64> = model.words().map(|w| model.log_likelihood(w, alphabet)).collect();
+        fits.sort_by(f64::total_cmp);
+        let plausible = fits[(fits.len() as f64 * PLAUSIBLE_QUANTILE) as usize];
+        Ok(Self {
+            model,
+            alphabet,
+            random: -(alphabet as f64).ln(),
+            plausible,
+        })
+    }
+
+    /// The score of `s`, from 0 to 1. Only letters count, and each run of
+    /// them is rated as a word of its own.
+    pub fn score(&self, s: &str) -> f64 {
+        let s = s.to_lowercase();
+        let words: Vec<&str> = s
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let letters: usize = words.iter().map(|w| w.chars().count()).sum();
+        if letters == 0 {
+            return 0.0;
+        }
+        // How well the letters follow the model, as a share of the way from
+        // random letters to those of a typical training word.
+        let fit = words
+            .iter()
+            .map(|w| {
+                let fit = (self.model.log_likelihood(w, self.alphabet) - self.random)
+                    / (self.plausible - self.random);
+                fit.clamp(0.0, 1.0) * w.chars().count() as f64
+            })
+            .sum::<f64>()
+            / letters as f64;
+
+        let longest_run = words
+            .iter()
+            .flat_map(|w| w.split(is_vowel))
+            .map(|run| run.chars().count())
+            .max()
+            .unwrap_or(0);
+        let runs = match longest_run.saturating_sub(EASY_CONSONANT_RUN) {
+            0 => 1.0,
+            1 => 0.5,
+            _ => 0.0,
+        };
+
+        let vowels = words
+            .iter()
+            .flat_map(|w| w.chars())
+            .filter(|c| is_vowel(*c))
+            .count() as f64
+            / letters as f64;
+        let balance = if vowels < 0.3 {
+            vowels / 0.3
+        } else if vowels > 0.6 {
+            (1.0 - vowels) / 0.4
+        } else {
+            1.0
+        };
+
+        0.5 * fit + 0.25 * runs + 0.25 * balance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_test() {
+        let scorer = Scorer::new(None).unwrap();
+        let (good, bad) = (scorer.score("Melora"), scorer.score("qxuaz"));
+        assert!(good > 0.8, "{}", good);
+        assert!(bad < 0.6, "{}", bad);
+        assert!(scorer.score("Tavern of Elinor") > scorer.score("Tvrn f Xqzk"));
+        assert_eq!(scorer.score("1234"), 0.0);
+        for s in ["a", "zzzzzz", "aeiou", "the Sunken Keep of Varrow"] {
+            assert!((0.0..=1.0).contains(&scorer.score(s)));
+        }
+    }
+}
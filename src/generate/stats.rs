@@ -0,0 +1,235 @@
+//! How big a pattern's language is and how evenly it gets sampled.
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+use super::node::Node;
+
+/// The number of distinct strings a pattern can generate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LanguageSize {
+    Exact(BigUint),
+    /// An upper bound, for patterns with anchors, word boundaries or
+    /// back-references that rule out some of the strings.
+    AtMost(BigUint),
+    /// The pattern can generate arbitrarily long strings.
+    Unbounded,
+}
+
+impl LanguageSize {
+    /// The number of strings, or the bound on it, if there is one.
+    pub fn count(&self) -> Option<&BigUint> {
+        match self {
+            Self::Exact(n) | Self::AtMost(n) => Some(n),
+            Self::Unbounded => None,
+        }
+    }
+}
+
+/// Statistics about a pattern and the way strings are sampled from it.
+///
+/// With uniform sampling, `entropy` and `collision` follow from the number
+/// of strings. Otherwise they treat every sequence of choices the generator
+/// can make as a different string. Either way they ignore strings thrown
+/// away for breaking an anchor or word boundary. A pattern that can produce
+/// the same string in several ways, like `a|a`, has less entropy and more
+/// duplicates than they report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub size: LanguageSize,
+    /// Bits of entropy in the choices made while generating a string.
+    pub entropy: f64,
+    /// Probability of two generated strings being the same.
+    pub collision: f64,
+}
+
+impl Stats {
+    /// The probability of at least one duplicate among `n` generated strings,
+    /// by the birthday bound.
+    pub fn duplicate_probability(&self, n: u32) -> f64 {
+        if let LanguageSize::Exact(size) = &self.size {
+            if BigUint::from(n) > *size {
+                return 1.0;
+            }
+        }
+        let pairs = n as f64 * (n as f64 - 1.0) / 2.0;
+        -(-pairs * self.collision).exp_m1()
+    }
+}
+
+/// `n` in bits, for `n` too large to fit a float.
+pub fn log2(n: &BigUint) -> f64 {
+    let shift = n.bits().saturating_sub(64);
+    (n >> shift).to_f64().unwrap_or(0.0).log2() + shift as f64
+}
+
+/// Entropy in bits and per-pair collision probability of a categorical
+/// distribution, where outcome `i` has probability `p[i]` and is followed by
+/// a choice with entropy `h[i]` and collision probability `q[i]`.
+fn mix(outcomes: impl Iterator<Item = (f64, f64, f64)>) -> (f64, f64) {
+    outcomes
+        .filter(|(p, _, _)| *p > 0.0)
+        .fold((0.0, 0.0), |(h, q), (p, hi, qi)| {
+            (h + p * (hi - p.log2()), q + p * p * qi)
+        })
+}
+
+/// Probability of each number of repetitions the generator can pick.
+fn repeat_counts(
+    min: u32,
+    max: Option<u32>,
+    probability: Option<f64>,
+    repeat: &[f64],
+) -> Vec<(u32, f64)> {
+    match (max, probability) {
+        (Some(1), Some(p)) if min == 0 => vec![(0, 1.0 - p), (1, p)],
+        (Some(max), _) => {
+            let max = max.max(min);
+            let p = 1.0 / (max - min + 1) as f64;
+            (min..=max).map(|k| (k, p)).collect()
+        }
+        (None, _) => repeat
+            .iter()
+            .enumerate()
+            .map(|(extra, p)| (min.saturating_add(extra as u32), *p))
+            .collect(),
+    }
+}
+
+/// Entropy and collision probability of generating from `node`, where
+/// `repeat` is the probability of each number of extra repetitions for
+/// open-ended quantifiers.
+pub fn sampling_stats(node: &Node, repeat: &[f64]) -> (f64, f64) {
+    match node {
+        // A back-reference repeats choices already made, and patterns with
+        // rules or sources have no statistics.
+        Node::Empty
+        | Node::Literal(_)
+        | Node::Assertion(_)
+        | Node::BackRef { .. }
+        | Node::Rule(_)
+        | Node::Source(_) => (0.0, 1.0),
+        Node::Class(cls) => {
+            let size: u32 = cls
+                .iter()
+                .map(|r| super::dfa::interval_len(r.start() as u32, r.end() as u32))
+                .sum();
+            ((size as f64).log2(), 1.0 / size as f64)
+        }
+        Node::Concat(nodes) => nodes
+            .iter()
+            .map(|n| sampling_stats(n, repeat))
+            .fold((0.0, 1.0), |(h, q), (hn, qn)| (h + hn, q * qn)),
+        Node::Alternation {
+            branches, weights, ..
+        } => {
+            let total: f64 = weights.iter().sum();
+            mix(branches.iter().zip(weights).map(|(b, w)| {
+                let (h, q) = sampling_stats(b, repeat);
+                (w / total, h, q)
+            }))
+        }
+        Node::Repetition {
+            node,
+            min,
+            max,
+            probability,
+        } => {
+            let (h, q) = sampling_stats(node, repeat);
+            mix(repeat_counts(*min, *max, *probability, repeat)
+                .into_iter()
+                .map(|(k, p)| (p, k as f64 * h, q.powi(k.to_i32().unwrap_or(i32::MAX)))))
+        }
+        Node::Group(node) | Node::Capture { node, .. } => sampling_stats(node, repeat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dfa::{Limits, MAX_STATES};
+    use super::super::regex_gen::{RegexGen, RegexGenOptions};
+    use super::*;
+    use crate::error::Error;
+    use crate::config::RepeatDist;
+
+    fn stats(pattern: &str, max_len: Option<usize>) -> Stats {
+        let options = RegexGenOptions {
+            repeat: RepeatDist::Uniform { cap: 3 },
+            ..Default::default()
+        };
+        RegexGen::with_options(pattern, &options)
+            .unwrap()
+            .stats(max_len)
+            .unwrap()
+    }
+
+    #[test]
+    fn language_size_test() {
+        let size = |p, max_len| stats(p, max_len).size;
+        assert_eq!(size("[a-z]{3}", None), LanguageSize::Exact(BigUint::from(17576u32)));
+        assert_eq!(size("a|a|b?", None), LanguageSize::Exact(BigUint::from(3u32)));
+        assert_eq!(size("(a|ab)(c|bc)", None), LanguageSize::Exact(BigUint::from(3u32)));
+        assert_eq!(size("x*", Some(5)), LanguageSize::Exact(BigUint::from(6u32)));
+        assert_eq!(size("x*", None), LanguageSize::Unbounded);
+        assert_eq!(size(r"\b(a| )", None), LanguageSize::AtMost(BigUint::from(2u32)));
+        assert_eq!(
+            size("[0-9a-f]{32}", None).count().unwrap().to_string(),
+            "340282366920938463463374607431768211456"
+        );
+    }
+
+    #[test]
+    fn entropy_test() {
+        let s = stats("[a-h]{4}", None);
+        assert!((s.entropy - 12.0).abs() < 1e-9);
+        assert!((s.collision - 1.0 / 4096.0).abs() < 1e-12);
+        assert!((stats("a|[b-i]", None).entropy - 2.5).abs() < 1e-9);
+        assert!((stats("({=3}a|b)", None).collision - 0.625).abs() < 1e-9);
+        // Two equally likely counts of one of two characters: 1 + 0.5 * 1.
+        assert!((stats("[ab]{0,1}", None).entropy - 1.5).abs() < 1e-9);
+        assert!((stats("a*", None).entropy - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn uniform_stats_test() {
+        let options = RegexGenOptions {
+            sampling: crate::config::Sampling::Uniform,
+            ..Default::default()
+        };
+        let s = RegexGen::with_options("a|[b-h]{2}", &options)
+            .unwrap()
+            .stats(None)
+            .unwrap();
+        assert!((s.entropy - 50f64.log2()).abs() < 1e-9);
+        assert!((s.collision - 1.0 / 50.0).abs() < 1e-12);
+        assert!((log2(&BigUint::from(3u32).pow(1000)) - 1000.0 * 3f64.log2()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn limits_test() {
+        let gen = RegexGen::new(r"\w{4,12}( \w{4,12}){0,3}").unwrap();
+        assert!(gen.stats(None).is_ok());
+        let limits = Limits {
+            states: 10,
+            deadline: None,
+        };
+        assert!(matches!(
+            gen.stats_within(None, &limits),
+            Err(Error::AnalysisLimit { .. })
+        ));
+        let limits = Limits::timed(MAX_STATES, std::time::Duration::from_secs(0));
+        assert!(matches!(
+            gen.stats_within(None, &limits),
+            Err(Error::AnalysisLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_probability_test() {
+        let s = stats("[0-9]{2}", None);
+        assert_eq!(s.duplicate_probability(1), 0.0);
+        assert_eq!(s.duplicate_probability(101), 1.0);
+        let p = s.duplicate_probability(10);
+        assert!((p - (1.0 - (-0.45f64).exp())).abs() < 1e-9);
+    }
+}
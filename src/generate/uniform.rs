@@ -0,0 +1,175 @@
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use rand::Rng;
+
+use super::dfa::{interval_len, nth_in_interval, Dfa};
+
+/// Draws strings uniformly from everything an automaton accepts within a
+/// range of lengths.
+///
+/// Every accepted string gets a rank in shortlex order; sampling draws a
+/// rank uniformly and decodes it by walking the automaton, using the
+/// number of ways each state can be completed.
+#[derive(Debug, Clone)]
+pub struct UniformSampler {
+    dfa: Dfa,
+    /// `ways[k][s]` is the number of strings of exactly `k` characters
+    /// that take state `s` to an accepting state.
+    ways: Vec<Vec<BigUint>>,
+    min_len: usize,
+    total: BigUint,
+}
+
+/// A uniformly random integer in `0..n`, for `n > 0`.
+fn gen_below<R: Rng + ?Sized>(rng: &mut R, n: &BigUint) -> BigUint {
+    if let Some(n) = n.to_u64() {
+        return BigUint::from(rng.gen_range(0, n));
+    }
+    let bits = n.bits();
+    let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        // Mask off the bits above `n`'s top bit, so at least half the draws
+        // are in range.
+        let last = bytes.len() - 1;
+        bytes[last] &= 0xFF >> (bytes.len() as u64 * 8 - bits);
+        let x = BigUint::from_bytes_le(&bytes);
+        if x < *n {
+            return x;
+        }
+    }
+}
+
+impl UniformSampler {
+    pub fn new(dfa: Dfa, min_len: usize, max_len: usize) -> Self {
+        let mut ways = vec![dfa
+            .states
+            .iter()
+            .map(|s| BigUint::from(s.accept as u32))
+            .collect::<Vec<_>>()];
+        for k in 1..=max_len {
+            let prev = &ways[k - 1];
+            let row = dfa
+                .states
+                .iter()
+                .map(|s| {
+                    s.trans
+                        .iter()
+                        .filter(|t| !prev[t.2].is_zero())
+                        .map(|t| &prev[t.2] * interval_len(t.0, t.1))
+                        .sum()
+                })
+                .collect();
+            ways.push(row);
+        }
+        let total = ways.iter().skip(min_len).map(|row| &row[dfa.start]).sum();
+        Self {
+            dfa,
+            ways,
+            min_len,
+            total,
+        }
+    }
+
+    /// Whether the automaton accepts no string short enough.
+    pub fn is_empty(&self) -> bool {
+        self.total.is_zero()
+    }
+
+    /// The string with shortlex rank `rank`, which must be below the total.
+    fn nth(&self, mut rank: BigUint) -> String {
+        let mut len = self.min_len;
+        while rank >= self.ways[len][self.dfa.start] {
+            rank -= &self.ways[len][self.dfa.start];
+            len += 1;
+        }
+        let mut s = String::with_capacity(len);
+        let mut state = self.dfa.start;
+        for remaining in (0..len).rev() {
+            let row = &self.ways[remaining];
+            for &(lo, hi, target) in &self.dfa.states[state].trans {
+                if row[target].is_zero() {
+                    continue;
+                }
+                let block = &row[target] * interval_len(lo, hi);
+                if rank < block {
+                    let offset = (&rank / &row[target]).to_u32().unwrap();
+                    rank %= &row[target];
+                    s.push(nth_in_interval(lo, offset));
+                    state = target;
+                    break;
+                }
+                rank -= block;
+            }
+        }
+        s
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> String {
+        self.nth(gen_below(rng, &self.total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::regex_gen::{RegexGen, RegexGenOptions};
+    use super::*;
+    use crate::config::Sampling;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+    use std::collections::HashMap;
+
+    fn uniform_counts(pattern: &str, max_len: Option<usize>, n: usize) -> HashMap<String, usize> {
+        let options = RegexGenOptions {
+            sampling: Sampling::Uniform,
+            max_len,
+            ..Default::default()
+        };
+        let gen = RegexGen::with_options(pattern, &options).unwrap();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let mut counts = HashMap::new();
+        for _ in 0..n {
+            *counts.entry(gen.randomize(&mut rng).unwrap()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn uniform_sampling_test() {
+        let counts = uniform_counts("a|[b-z]{5}", None, 1000);
+        assert!(!counts.contains_key("a"));
+
+        let counts = uniform_counts("[ab]|a|[ab]{2}", None, 6000);
+        assert_eq!(counts.len(), 6);
+        for (s, n) in &counts {
+            assert!(*n > 850 && *n < 1150, "{:?} drawn {} times", s, n);
+        }
+
+        let counts = uniform_counts(r"x*\b", Some(3), 3000);
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|n| *n > 850 && *n < 1150), "{:?}", counts);
+    }
+
+    #[test]
+    fn uniform_errors_test() {
+        let options = RegexGenOptions {
+            sampling: Sampling::Uniform,
+            ..Default::default()
+        };
+        assert!(RegexGen::with_options("a+", &options).is_err());
+        let options = RegexGenOptions {
+            max_len: Some(2),
+            ..options
+        };
+        assert!(RegexGen::with_options("abc", &options).is_err());
+    }
+
+    #[test]
+    fn gen_below_test() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let n = BigUint::from(3u32) << 70;
+        for _ in 0..100 {
+            assert!(gen_below(&mut rng, &n) < n);
+        }
+    }
+}
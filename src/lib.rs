@@ -4,6 +4,7 @@ extern crate lazy_static;
 pub mod cmdargs;
 pub mod pattern;
 pub mod config;
+pub mod error;
 pub mod generate;
 pub mod output;
 pub mod xform;
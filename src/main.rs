@@ -1,52 +1,99 @@
 use string_studio::generate::*;
 use string_studio::output::*;
 use string_studio::config::*;
+use string_studio::error::Error;
 
 use string_studio::cmdargs::*;
 
-fn create_config(overwrite: bool) -> Result<(), String> {
+fn create_config(overwrite: bool) -> Result<(), Error> {
     if let Some(path) = get_cfg_file_path() {
         if path.is_file() && !overwrite {
             return Ok(());
         }
         let config = Config::default();
-        let dir = path.parent();
-        if dir.is_none() {
-            return Err(String::from(
-                "Could not find directory portion of config file path!",
-            ));
-        }
-        let dir = dir.unwrap();
-        if !dir.is_dir() {
-            if let Err(err) = std::fs::create_dir_all(path.parent().unwrap()) {
-                return Err(err.to_string());
+        if let Some(dir) = path.parent() {
+            if !dir.is_dir() {
+                if let Err(source) = std::fs::create_dir_all(dir) {
+                    return Err(Error::ConfigIo { path, source });
+                }
             }
         }
-        if let Ok(contents) = serde_json::to_string_pretty(&config) {
-            if let Err(err) = std::fs::write(path, contents) {
-                return Err(err.to_string());
-            }
-        } else {
-            return Err(String::from("Error serializing default config!"));
+        let contents = match serde_json::to_string_pretty(&config) {
+            Ok(contents) => contents,
+            Err(source) => return Err(Error::ConfigJson { path, source }),
+        };
+        if let Err(source) = std::fs::write(&path, contents) {
+            return Err(Error::ConfigIo { path, source });
         }
     }
     Ok(())
 }
 
+/// Print `err`, with a caret under the offending part of the pattern when
+/// the error has a location.
+fn report_error(err: &Error) {
+    println_err(format!("Error: {}", err).as_str());
+    if let Some((pattern, span)) = err.location() {
+        let pad = pattern[..span.start].chars().count();
+        let width = pattern[span].chars().count().max(1);
+        println_err(format!("    {}", pattern).as_str());
+        println_err(format!("    {}{}", " ".repeat(pad), "^".repeat(width)).as_str());
+    }
+}
 
-fn run() -> Result<(), String> {
+fn run() -> Result<(), Error> {
     create_config(false)?;
     let (action, config) = process_args()?;
 
     match action {
         AppAction::Generate => {
             println_v2(&config, format!("Full Configuration: {}", config).as_str());
+            if config.number == 0 {
+                #[cfg(feature = "table_format")]
+                if config.format == OutputFormat::Table {
+                    return Err(Error::Args(String::from(
+                        "The table format needs a `--number` other than 0",
+                    )));
+                }
+                println_v1(&config, "Generating strings until the output is closed...\n");
+            } else {
+                println_v1(
+                    &config,
+                    format!("Generating {} strings...\n", config.number).as_str(),
+                );
+            }
+            if config.rank.is_some() {
+                output_records(&config, generate_ranked(&config)?.into_iter().map(Ok))?;
+            } else {
+                output_records(&config, generate_iter(&config)?)?;
+            }
+        }
+        AppAction::Enumerate => {
+            println_v2(&config, format!("Full Configuration: {}", config).as_str());
+            output_iter(&config, enumerate_iter(&config)?.map(Ok))?;
+        }
+        AppAction::Stats => {
+            println_v2(&config, format!("Full Configuration: {}", config).as_str());
+            let stats = stats(&config)?;
+            output_stats(&config, &stats)?;
+        }
+        AppAction::Train {
+            words,
+            model,
+            order,
+        } => {
+            let markov = markov::MarkovModel::train_file(&words, order)?;
+            markov.save(&model)?;
             println_v1(
                 &config,
-                format!("Generating {} strings...\n", config.number).as_str(),
+                format!(
+                    "Trained an order {} model on {} words, saved to `{}`",
+                    order,
+                    markov.word_count(),
+                    model.display()
+                )
+                .as_str(),
             );
-            let strings = generate(&config)?;
-            output(&config, &strings)?;
         }
         AppAction::DumpConfig => {
             create_config(true)?;
@@ -60,7 +107,7 @@ fn run() -> Result<(), String> {
 fn main() {
     let result = run();
     if let Err(err) = result {
-        println_err(format!("Error: {}", err).as_str());
+        report_error(&err);
         std::process::exit(1);
     }
 }
@@ -1,52 +1,299 @@
+use std::io::{self, Write};
+
 use crate::config::*;
+use crate::error::Error;
+use crate::generate::regex_gen::Record;
+use crate::generate::stats::{LanguageSize, Stats};
+
+type Records<'a> = dyn Iterator<Item = Record> + 'a;
 
-pub fn output_simple(_config: &Config, strings: &Vec<String>) -> Result<(), String> {
-    for s in strings {
-        println!("{}", s);
+fn write_simple(records: &mut Records, out: &mut dyn Write) -> io::Result<()> {
+    for r in records {
+        writeln!(out, "{}", r.text)?;
     }
     Ok(())
 }
 
 #[cfg(feature = "table_format")]
-pub fn output_table(_config: &Config, strings: &Vec<String>) -> Result<(), String> {
+fn write_table(records: &mut Records, out: &mut dyn Write) -> io::Result<()> {
     use prettytable::{Cell, Row, Table};
     // Create the table
     let mut table = Table::new();
 
+    let records: Vec<Record> = records.collect();
+    if let Some(first) = records
+        .iter()
+        .find(|r| !r.fields.is_empty() || r.score.is_some())
+    {
+        // With named groups or scores, a row per string with a column for
+        // each group and one for the score.
+        let scored = records.iter().any(|r| r.score.is_some());
+        let titles = std::iter::once("text")
+            .chain(first.fields.iter().map(|(n, _)| n.as_str()))
+            .chain(scored.then_some("score"))
+            .map(Cell::new)
+            .collect();
+        table.set_titles(Row::new(titles));
+        for r in &records {
+            let score = r.score.map(|s| format!("{:.3}", s)).unwrap_or_default();
+            let cells = std::iter::once(Cell::new(&r.text))
+                .chain(r.fields.iter().map(|(_, t)| Cell::new(t.as_deref().unwrap_or(""))))
+                .chain(scored.then(|| Cell::new(&score)))
+                .collect();
+            table.add_row(Row::new(cells));
+        }
+        return table.print(out).map(|_| ());
+    }
+    let strings: Vec<String> = records.into_iter().map(|r| r.text).collect();
     let rows_iter = strings.chunks(4);
     for r in rows_iter {
         table.add_row(Row::new(r.iter().map(|x| Cell::new(x)).collect()));
     }
 
-    // Print the table to stdout
-    table.printstd();
+    table.print(out).map(|_| ())
+}
 
-    Ok(())
+/// A score rounded to the precision it's output with.
+fn round_score(score: f64) -> f64 {
+    (score * 1000.0).round() / 1000.0
 }
-pub fn output_json(config: &Config, strings: &Vec<String>) -> Result<(), String> {
-    let s = if config.pretty {
-        serde_json::to_string_pretty(strings)
+
+/// A record as a JSON string, or as an object of its text, named groups and
+/// score if it has any, laid out the way `serde_json` would at the first
+/// level of nesting.
+fn json_record(config: &Config, r: &Record) -> serde_json::Result<String> {
+    if r.fields.is_empty() && r.score.is_none() {
+        return serde_json::to_string(&r.text);
+    }
+    let (sep, indent, end) = if config.pretty {
+        (",\n", "    ", "\n  ")
     } else {
-        serde_json::to_string(strings)
+        (",", "", "")
     };
-    if let Ok(s) = s {
-        println!("{}", s);
-        Ok(())
+    let colon = if config.pretty { ": " } else { ":" };
+    let mut s = String::from("{");
+    let mut entries = vec![("text", serde_json::to_string(&r.text)?)];
+    for (name, text) in &r.fields {
+        entries.push((name, serde_json::to_string(text)?));
+    }
+    if let Some(score) = r.score {
+        entries.push(("score", serde_json::to_string(&round_score(score))?));
+    }
+    for (i, (name, value)) in entries.into_iter().enumerate() {
+        let lead = if i == 0 && config.pretty { "\n" } else if i == 0 { "" } else { sep };
+        s += &format!(
+            "{}{}{}{}{}",
+            lead,
+            indent,
+            serde_json::to_string(name)?,
+            colon,
+            value
+        );
+    }
+    s += end;
+    s += "}";
+    Ok(s)
+}
+
+fn write_json(config: &Config, records: &mut Records, out: &mut dyn Write) -> io::Result<()> {
+    // Written an element at a time, laid out the way `serde_json` would.
+    let (sep, indent, end) = if config.pretty {
+        (",\n", "  ", "\n")
     } else {
-        Err(String::from("Unknown JSON error!"))
+        (",", "", "")
+    };
+    write!(out, "[")?;
+    for (i, r) in records.enumerate() {
+        let s = json_record(config, &r).map_err(io::Error::other)?;
+        let sep = if i == 0 { end } else { sep };
+        write!(out, "{}{}{}", sep, indent, s)?;
+        out.flush()?;
     }
+    writeln!(out, "{}]", end)
 }
-pub fn output_csv(_config: &Config, strings: &Vec<String>) -> Result<(), String> {
-    let csv = strings.join(",");
-    println!("{}", csv);
+
+/// `s` as a CSV field, quoted if it needs to be.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+fn write_csv(records: &mut Records, out: &mut dyn Write) -> io::Result<()> {
+    let mut records = records.peekable();
+    let (names, scored): (Vec<String>, bool) = match records.peek() {
+        Some(r) if !r.fields.is_empty() || r.score.is_some() => (
+            r.fields.iter().map(|(n, _)| n.clone()).collect(),
+            r.score.is_some(),
+        ),
+        _ => {
+            for (i, r) in records.enumerate() {
+                write!(out, "{}{}", if i == 0 { "" } else { "," }, r.text)?;
+                out.flush()?;
+            }
+            return writeln!(out);
+        }
+    };
+    // With named groups or scores, a row per string, with a column for each
+    // group and one for the score.
+    let header: Vec<String> = std::iter::once("text")
+        .chain(names.iter().map(String::as_str))
+        .chain(scored.then_some("score"))
+        .map(csv_field)
+        .collect();
+    writeln!(out, "{}", header.join(","))?;
+    for r in records {
+        let row: Vec<String> = std::iter::once(csv_field(&r.text))
+            .chain(r.fields.iter().map(|(_, t)| csv_field(t.as_deref().unwrap_or(""))))
+            .chain(r.score.map(|score| round_score(score).to_string()))
+            .collect();
+        writeln!(out, "{}", row.join(","))?;
+        out.flush()?;
+    }
     Ok(())
 }
-pub fn output(config: &Config, strings: &Vec<String>) -> Result<(), String> {
+
+/// Write `strings` to stdout in the configured format as they are produced.
+///
+/// Stops at the first error from `strings`, and quietly if stdout gets
+/// closed, as when piping into `head`.
+pub fn output_iter<I>(config: &Config, strings: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Result<String, Error>>,
+{
+    output_records(config, strings.into_iter().map(|r| r.map(Record::from)))
+}
+
+/// Names that named groups can't have outside the `simple` format, where
+/// they'd clash with the string's own member or column.
+const RESERVED_FIELDS: &[&str] = &["text", "score"];
+
+/// Write `records` to stdout like `output_iter`, with the text of their named
+/// groups and their scores as object members in `json` and extra columns in
+/// `csv` and `table`.
+pub fn output_records<I>(config: &Config, records: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    let mut error = None;
+    let result = {
+        let mut strings = records
+            .into_iter()
+            .map_while(|r| match r {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            })
+            .peekable();
+        let fields = match strings.peek() {
+            Some(r) if config.format != OutputFormat::Simple => r.fields.as_slice(),
+            _ => &[],
+        };
+        if let Some((name, _)) = fields
+            .iter()
+            .find(|(name, _)| RESERVED_FIELDS.contains(&name.as_str()))
+        {
+            return Err(Error::Config(format!(
+                "A named group can't be called `{0}` outside the simple format, where `{0}` already holds the string's own {0}; rename the group",
+                name
+            )));
+        }
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        match config.format {
+            #[cfg(feature = "table_format")]
+            OutputFormat::Table => write_table(&mut strings, &mut out),
+            OutputFormat::Simple => write_simple(&mut strings, &mut out),
+            OutputFormat::Json => write_json(config, &mut strings, &mut out),
+            OutputFormat::Csv => write_csv(&mut strings, &mut out),
+        }
+    };
+    if let Some(e) = error {
+        return Err(e);
+    }
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(Error::Output(format!("Failed to write output: {}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn output(config: &Config, strings: &[String]) -> Result<(), Error> {
+    output_iter(config, strings.iter().cloned().map(Ok))
+}
+
+/// Print the size and sampling statistics of a pattern.
+pub fn output_stats(config: &Config, stats: &Stats) -> Result<(), Error> {
+    let (size, exact) = match &stats.size {
+        LanguageSize::Exact(n) => (Some(n.to_string()), true),
+        LanguageSize::AtMost(n) => (Some(n.to_string()), false),
+        LanguageSize::Unbounded => (None, false),
+    };
+    let duplicates = stats.duplicate_probability(config.number);
     match config.format {
-        #[cfg(feature = "table_format")]
-        OutputFormat::Table => output_table(config, strings),
-        OutputFormat::Simple => output_simple(config, strings),
-        OutputFormat::Json => output_json(config, strings),
-        OutputFormat::Csv => output_csv(config, strings),
+        OutputFormat::Json => {
+            let value = serde_json::json!({
+                "strings": size,
+                "exact": exact,
+                "max_len": config.max_len,
+                "entropy_bits": stats.entropy,
+                "number": config.number,
+                "duplicate_probability": duplicates,
+            });
+            let s = if config.pretty {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            match s {
+                Ok(s) => println!("{}", s),
+                Err(_) => return Err(Error::Output(String::from("Unknown JSON error!"))),
+            }
+        }
+        OutputFormat::Csv => {
+            println!("strings,exact,entropy_bits,number,duplicate_probability");
+            println!(
+                "{},{},{},{},{}",
+                size.unwrap_or_default(),
+                exact,
+                stats.entropy,
+                config.number,
+                duplicates
+            );
+        }
+        _ => {
+            let within = match config.max_len {
+                Some(len) => format!(" of up to {} characters", len),
+                None => String::new(),
+            };
+            match (&stats.size, size) {
+                (LanguageSize::Exact(_), Some(n)) => println!("Distinct strings{}: {}", within, n),
+                (_, Some(n)) => println!(
+                    "Distinct strings{}: at most {} (some break an anchor, word boundary or back-reference)",
+                    within, n
+                ),
+                _ => println!("Distinct strings: unbounded"),
+            }
+            println!("Entropy: {:.2} bits", stats.entropy);
+            println!(
+                "Chance of a duplicate in {} strings: {}",
+                config.number,
+                format_probability(duplicates)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn format_probability(p: f64) -> String {
+    if p > 0.0 && p < 1e-4 {
+        format!("{:.2e}", p)
+    } else {
+        format!("{:.2}%", p * 100.0)
     }
 }
@@ -1,39 +1,158 @@
-use crate::config::Fragment;
+use crate::config::{Fragment, WordList};
+use crate::error::Error;
+use crate::generate::extension::{scan_groups, GroupToken};
 use serde::de::Deserializer;
 use serde::de::{SeqAccess, Visitor};
 use serde::ser::{SerializeSeq, Serializer};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::iter::FromIterator;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+/// The word lists and backends that the `{&name}` extensions of an
+/// assembled pattern take their entries from.
+#[derive(Debug, Default)]
+pub struct Sources {
+    /// Directory that relative word list paths are resolved against.
+    pub dir: Option<PathBuf>,
+    pub word_lists: Vec<WordList>,
+    /// Names of the fragments generated with a backend of their own.
+    pub backends: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct CompositePattern {
     pub parts: Vec<String>,
 }
 
 impl CompositePattern {
-    pub fn assemble_pattern(&self, fragments: &Vec<Fragment>) -> Result<String, String> {
+    pub fn assemble_pattern(&self, fragments: &[Fragment]) -> Result<String, Error> {
+        self.assemble(fragments, &mut Sources::default())
+    }
+
+    /// Assemble the pattern, adding the word list of each word list
+    /// fragment it uses, and the name of each fragment with a backend of its
+    /// own, to `sources` unless they're there already.
+    pub fn assemble(&self, fragments: &[Fragment], sources: &mut Sources) -> Result<String, Error> {
         let mut pat = String::new();
+        self.assemble_into(fragments, &mut pat, &mut 0, sources)?;
+        Ok(pat)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Append the assembled pattern to `pat`, which has `groups` capture
+    /// groups so far.
+    ///
+    /// Numbered back-references count only the groups in this pattern's own
+    /// parts, and get renumbered to count every group in `pat`.
+    fn assemble_into(
+        &self,
+        fragments: &[Fragment],
+        pat: &mut String,
+        groups: &mut usize,
+        sources: &mut Sources,
+    ) -> Result<(), Error> {
+        // The number in `pat` of each of this pattern's groups.
+        let mut local: Vec<usize> = Vec::new();
         for p in &self.parts {
-            if p.starts_with("@") && p.ends_with("@") {
-                let p_name = p.strip_suffix("@").unwrap().strip_prefix("@").unwrap();
-                pat += &{
-                    let mut s: String = Default::default();
-                    for i in fragments {
-                        if i.name == p_name {
-                            let tmp_pat = i.pattern.assemble_pattern(fragments)?;
-                            s = tmp_pat;
-                            break;
-                        }
+            if p.len() > 1 && p.starts_with('@') && p.ends_with('@') {
+                let p_name = &p[1..p.len() - 1];
+                match fragments.iter().find(|i| i.name == p_name) {
+                    Some(i) => assemble_fragment(i, fragments, pat, groups, sources)?,
+                    None => {
+                        let start = pat.len();
+                        return Err(Error::UnknownFragment {
+                            pattern: format!("{}{}", pat, p),
+                            name: String::from(p_name),
+                            span: start..start + p.len(),
+                        });
                     }
-                    s.clone()
-                };
+                }
             } else {
-                pat += &p;
+                append_part(p, pat, groups, &mut local);
             }
         }
-        Ok(pat)
+        Ok(())
+    }
+}
+
+/// Append `part` to `pat`, counting its capture groups and renumbering its
+/// back-references from `local` numbering to numbering across `pat`.
+fn append_part(part: &str, pat: &mut String, groups: &mut usize, local: &mut Vec<usize>) {
+    let mut copied = 0;
+    for token in scan_groups(part) {
+        match token {
+            GroupToken::Open => {
+                *groups += 1;
+                local.push(*groups);
+            }
+            GroupToken::Reference { number, span } => {
+                // References to later groups are numbered as if the groups
+                // in between were all local, and `\0` is left alone, for the
+                // errors to point at.
+                let absolute = match local.get(number.wrapping_sub(1)) {
+                    Some(n) => *n,
+                    None if number == 0 => 0,
+                    None => *groups + number - local.len(),
+                };
+                *pat += &part[copied..span.start];
+                *pat += &format!("\\{}", absolute);
+                copied = span.end;
+            }
+        }
+    }
+    *pat += &part[copied..];
+}
+
+/// Append the pattern of fragment `f`, writing its alternatives, word list,
+/// backend and probability with the weight, word list and probability
+/// extensions.
+fn assemble_fragment(
+    f: &Fragment,
+    fragments: &[Fragment],
+    pat: &mut String,
+    groups: &mut usize,
+    sources: &mut Sources,
+) -> Result<(), Error> {
+    if f.probability.is_some() {
+        *pat += "(?:";
     }
+    if f.backend.is_some() {
+        if !sources.backends.contains(&f.name) {
+            sources.backends.push(f.name.clone());
+        }
+        *pat += &format!("{{&{}}}", f.name);
+    } else if let Some(path) = &f.wordlist {
+        // Only lists that get used are read, so a missing file only matters
+        // to patterns that need it.
+        if !sources.word_lists.iter().any(|l| l.name == f.name) {
+            let path = match &sources.dir {
+                Some(dir) => dir.join(path),
+                None => path.clone(),
+            };
+            sources.word_lists.push(WordList::load(&f.name, &path)?);
+        }
+        *pat += &format!("{{&{}}}", f.name);
+    } else if f.alternatives.is_empty() {
+        f.pattern.assemble_into(fragments, pat, groups, sources)?;
+    } else {
+        *pat += "(?:";
+        for (i, alt) in f.alternatives.iter().enumerate() {
+            if i > 0 {
+                *pat += "|";
+            }
+            *pat += &format!("{{={}}}", alt.weight);
+            alt.pattern.assemble_into(fragments, pat, groups, sources)?;
+        }
+        *pat += ")";
+    }
+    if let Some(p) = f.probability {
+        *pat += &format!("){{?{}}}", p);
+    }
+    Ok(())
 }
 
 impl Serialize for CompositePattern {
@@ -41,7 +160,7 @@ impl Serialize for CompositePattern {
     where
         S: Serializer,
     {
-        if self.parts.len() < 1 {
+        if self.parts.is_empty() {
             serializer.serialize_none()
         } else if self.parts.len() == 1 {
             serializer.serialize_str(&self.parts[0])
@@ -79,12 +198,8 @@ impl<'de> Deserialize<'de> for CompositePattern {
                 V: SeqAccess<'de>,
             {
                 let mut strs: Vec<String> = Vec::new();
-                loop {
-                    if let Ok(Some(s)) = seq.next_element::<&str>() {
-                        strs.push(String::from(s));
-                    } else {
-                        break;
-                    }
+                while let Ok(Some(s)) = seq.next_element::<&str>() {
+                    strs.push(String::from(s));
                 }
                 Ok(CompositePattern::from(strs.as_slice()))
             }
@@ -96,12 +211,6 @@ impl<'de> Deserialize<'de> for CompositePattern {
 }
 
 
-impl Default for CompositePattern {
-    fn default() -> Self {
-        Self { parts: Vec::new() }
-    }
-}
-
 impl From<&str> for CompositePattern {
     fn from(value: &str) -> Self {
         Self {
@@ -124,3 +233,78 @@ impl From<&[&str]> for CompositePattern {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Alternative;
+
+    #[test]
+    fn assemble_pattern_test() {
+        let fragments = vec![
+            Fragment::new("vowel", CompositePattern::from("[aeiou]"), ""),
+            Fragment::new("cv", CompositePattern::from(&["b", "@vowel@"] as &[&str]), ""),
+        ];
+        let pat = CompositePattern::from(&["x", "@cv@", "@vowel@"] as &[&str]);
+        assert_eq!(pat.assemble_pattern(&fragments).unwrap(), "xb[aeiou][aeiou]");
+    }
+
+    #[test]
+    fn assemble_backref_test() {
+        let fragments = vec![
+            Fragment::new("ab", CompositePattern::from("(a|b)(c)\\2"), ""),
+        ];
+        let pat = CompositePattern::from(&["(x)", "@ab@", "(y)\\2\\1\\3"] as &[&str]);
+        assert_eq!(
+            pat.assemble_pattern(&fragments).unwrap(),
+            "(x)(a|b)(c)\\3(y)\\4\\1\\5"
+        );
+    }
+
+    #[test]
+    fn assemble_weighted_fragment_test() {
+        let fragments = vec![
+            Fragment {
+                name: String::from("end"),
+                alternatives: vec![
+                    Alternative::new(CompositePattern::from("[bcd]"), 4.0),
+                    Alternative::new(CompositePattern::from("[ae]"), 0.5),
+                ],
+                ..Default::default()
+            },
+            Fragment {
+                name: String::from("apostrophe"),
+                pattern: CompositePattern::from("'"),
+                probability: Some(0.1),
+                ..Default::default()
+            },
+        ];
+        let pat = CompositePattern::from(&["k", "@apostrophe@", "@end@"] as &[&str]);
+        assert_eq!(
+            pat.assemble_pattern(&fragments).unwrap(),
+            "k(?:'){?0.1}(?:{=4}[bcd]|{=0.5}[ae])"
+        );
+    }
+
+    #[test]
+    fn assemble_unknown_fragment_test() {
+        let fragments = vec![Fragment::new(
+            "cv",
+            CompositePattern::from(&["b", "@vowel@"] as &[&str]),
+            "",
+        )];
+        let pat = CompositePattern::from(&["x", "@cv@"] as &[&str]);
+        match pat.assemble_pattern(&fragments) {
+            Err(Error::UnknownFragment {
+                pattern,
+                name,
+                span,
+            }) => {
+                assert_eq!(pattern, "xb@vowel@");
+                assert_eq!(name, "vowel");
+                assert_eq!(span, 2..9);
+            }
+            r => panic!("expected unknown fragment error, got {:?}", r),
+        }
+    }
+}
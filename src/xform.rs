@@ -2,7 +2,7 @@ use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 pub use std::convert::TryFrom;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Xform {
     UAfterQ,
     LowerCase,
@@ -17,7 +17,7 @@ impl Xform {
         }
 
         let do_replace = |caps: &Captures| -> String {
-            if let None = caps.get(2) {
+            if caps.get(2).is_none() {
                 let s = caps.get(1).unwrap().as_str();
                 format!("{}u", s)
             } else {